@@ -66,8 +66,7 @@ fn main() {
         .flat_map(|&day| [(day, 1), (day, 2)])
         .map(|(day, part)| {
             lazy_format!(
-                "(Day::Day{day}, Part::Part{part}) => input
-                    .try_into()
+                "(Day::Day{day}, Part::Part{part}) => FromPuzzleInput::from_puzzle_input(input, options)
                     .context(\"failed to parse input\")
                     .and_then(|input| day{day}::part{part}(input).context(\"failed to compute solution after successful parse\"))
                     .context(\"failed to solve day {day}, part {part}\")
@@ -100,7 +99,7 @@ fn main() {
         }}
 
 
-        fn run_solution(day: Day, part: Part, input: &str) -> anyhow::Result<()> {{
+        fn run_solution(day: Day, part: Part, input: &mut PuzzleInput, options: &Options) -> anyhow::Result<()> {{
             match (day, part) {{
                 {solver_match_arms}
             }}
@@ -1,17 +1,23 @@
-use std::cmp::max;
+use std::{
+    cmp::{max, Reverse},
+    io::BufRead,
+};
 
 use anyhow::Context;
-use nom::{branch::alt, character::complete::digit1, combinator::eof, IResult, Parser};
+use nom::{IResult, Parser};
 use nom_supreme::{
     error::ErrorTree,
     final_parser::{final_parser, Location},
-    multi::parse_separated_terminated,
-    tag::complete::tag,
-    ParserExt,
+};
+
+use crate::{
+    input::{FromPuzzleInput, InputStream, PuzzleInput},
+    library::parse::unsigned,
+    Options,
 };
 
 fn parse_meal(input: &str) -> IResult<&str, i64, ErrorTree<&str>> {
-    digit1.parse_from_str().parse(input)
+    unsigned().parse(input)
 }
 
 trait ElfCollect {
@@ -19,20 +25,6 @@ trait ElfCollect {
     fn add(&mut self, meal: i64);
 }
 
-fn parse_elf<T: ElfCollect>(input: &str) -> IResult<&str, T, ErrorTree<&str>> {
-    parse_separated_terminated(
-        parse_meal.context("meal"),
-        tag("\n"),
-        alt((eof.value(()), tag("\n\n").value(()))).peek(),
-        T::new,
-        |mut elf, meal| {
-            elf.add(meal);
-            elf
-        },
-    )
-    .parse(input)
-}
-
 trait ElfSet {
     type Elf;
 
@@ -40,28 +32,63 @@ trait ElfSet {
     fn add(&mut self, elf: Self::Elf);
 }
 
-fn parse_elves<T: ElfSet>(input: &str) -> IResult<&str, T, ErrorTree<&str>>
+/// Read the elf list one line at a time, handing each elf to the set as soon
+/// as it's complete, so that only one line is ever held in memory. Elves are
+/// separated by blank lines.
+fn read_elves<T: ElfSet>(mut input: impl BufRead) -> anyhow::Result<T>
 where
     T::Elf: ElfCollect,
 {
-    parse_separated_terminated(
-        parse_elf.context("elf"),
-        tag("\n\n"),
-        eof,
-        T::new,
-        |mut set, elf| {
-            set.add(elf);
-            set
-        },
-    )
-    .parse(input)
-}
-
-fn final_parse_elves<T: ElfSet>(input: &str) -> Result<T, ErrorTree<Location>>
-where
-    T::Elf: ElfCollect,
-{
-    final_parser(parse_elves)(input)
+    let mut set = T::new();
+    let mut elf: Option<T::Elf> = None;
+    let mut elves = 0;
+    let mut line = String::new();
+
+    for line_number in 1.. {
+        line.clear();
+
+        let count = input
+            .read_line(&mut line)
+            .with_context(|| format!("failed to read line {line_number}"))?;
+
+        if count == 0 {
+            break;
+        }
+
+        match line.trim() {
+            "" => {
+                if let Some(elf) = elf.take() {
+                    set.add(elf);
+                    elves += 1;
+                }
+            }
+            meal => {
+                // The meal is parsed on its own, so its error locations are
+                // moved to where it actually is in the input
+                let indent = line.len() - line.trim_start().len();
+                let meal: Result<i64, ErrorTree<Location>> = final_parser(parse_meal)(meal);
+                let meal = meal
+                    .map_err(|err| {
+                        err.map_locations(|location| Location {
+                            line: line_number,
+                            column: location.column + indent,
+                        })
+                    })
+                    .context("failed to parse meal")?;
+
+                elf.get_or_insert_with(T::Elf::new).add(meal);
+            }
+        }
+    }
+
+    if let Some(elf) = elf {
+        set.add(elf);
+        elves += 1;
+    }
+
+    anyhow::ensure!(elves > 0, "the input doesn't list any elves");
+
+    Ok(set)
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
@@ -96,9 +123,150 @@ impl ElfSet for BestElf {
     }
 }
 
-pub fn part1(input: &str) -> anyhow::Result<i64> {
-    final_parse_elves(input.trim())
-        .context("failed to parse elf list")
+#[derive(Debug, Clone, Copy, Default)]
+struct ElfMeals {
+    total: i64,
+    meals: usize,
+}
+
+impl ElfCollect for ElfMeals {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(&mut self, meal: i64) {
+        self.total += meal;
+        self.meals += 1;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ElfRecord {
+    /// The position of this elf in the input, starting from 1
+    index: usize,
+    total: i64,
+    meals: usize,
+}
+
+/// An `ElfSet` that keeps every elf, for when we need more than just the
+/// answer. Unlike the other sets, this uses memory proportional to the number
+/// of elves.
+#[derive(Debug, Default)]
+struct CalorieReport {
+    elves: Vec<ElfRecord>,
+}
+
+impl ElfSet for CalorieReport {
+    type Elf = ElfMeals;
+
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(&mut self, elf: Self::Elf) {
+        self.elves.push(ElfRecord {
+            index: self.elves.len() + 1,
+            total: elf.total,
+            meals: elf.meals,
+        })
+    }
+}
+
+impl CalorieReport {
+    /// All of the elves, from most to fewest calories. Elves with the same
+    /// total stay in input order.
+    fn ranking(&self) -> Vec<ElfRecord> {
+        let mut ranking = self.elves.clone();
+        ranking.sort_by_key(|elf| Reverse(elf.total));
+        ranking
+    }
+
+    fn mean(&self) -> Option<f64> {
+        let total: i64 = self.elves.iter().map(|elf| elf.total).sum();
+
+        match self.elves.len() {
+            0 => None,
+            count => Some(total as f64 / count as f64),
+        }
+    }
+
+    fn median(&self) -> Option<f64> {
+        let mut totals: Vec<i64> = self.elves.iter().map(|elf| elf.total).collect();
+        totals.sort_unstable();
+
+        let middle = totals.len() / 2;
+
+        match totals.len() {
+            0 => None,
+            len if len % 2 == 1 => Some(totals[middle] as f64),
+            _ => Some((totals[middle - 1] + totals[middle]) as f64 / 2.0),
+        }
+    }
+
+    /// The combined calories of the `k` elves with the most calories
+    fn top_k(&self, k: usize) -> i64 {
+        self.ranking().iter().take(k).map(|elf| elf.total).sum()
+    }
+
+    fn print(&self, k: usize) {
+        eprintln!("elves: {}", self.elves.len());
+
+        if let (Some(mean), Some(median)) = (self.mean(), self.median()) {
+            eprintln!("mean: {mean:.1}");
+            eprintln!("median: {median:.1}");
+        }
+
+        eprintln!("top {k}: {}", self.top_k(k));
+
+        for (rank, elf) in self.ranking().iter().enumerate() {
+            eprintln!(
+                "#{rank}: elf {index}, {total} calories in {meals} meals",
+                rank = rank + 1,
+                index = elf.index,
+                total = elf.total,
+                meals = elf.meals,
+            );
+        }
+    }
+}
+
+pub struct Input {
+    stream: InputStream,
+    top_k: Option<usize>,
+    calorie_report: bool,
+}
+
+impl FromPuzzleInput<'_> for Input {
+    fn from_puzzle_input(input: &mut PuzzleInput, options: &Options) -> anyhow::Result<Self> {
+        Ok(Self {
+            stream: input.stream(),
+            top_k: options.top_k,
+            calorie_report: options.calorie_report,
+        })
+    }
+}
+
+impl Input {
+    /// Read every elf into a `CalorieReport`, print it if requested, and use
+    /// it to total the top `k` elves.
+    fn solve_with_report(self, k: usize) -> anyhow::Result<i64> {
+        let report: CalorieReport = read_elves(self.stream).context("failed to read elf list")?;
+
+        if self.calorie_report {
+            report.print(k);
+        }
+
+        Ok(report.top_k(k))
+    }
+}
+
+pub fn part1(input: Input) -> anyhow::Result<i64> {
+    if input.calorie_report {
+        return input.solve_with_report(1);
+    }
+
+    read_elves(input.stream)
+        .context("failed to read elf list")
         .map(|best: BestElf| best.elf.total)
 }
 
@@ -122,8 +290,13 @@ impl ElfSet for Best3 {
     }
 }
 
-pub fn part2(input: &str) -> anyhow::Result<i64> {
-    final_parse_elves(input.trim())
-        .context("failed to parse elf list")
+pub fn part2(input: Input) -> anyhow::Result<i64> {
+    if input.calorie_report || input.top_k.is_some() {
+        let k = input.top_k.unwrap_or(3);
+        return input.solve_with_report(k);
+    }
+
+    read_elves(input.stream)
+        .context("failed to read elf list")
         .map(|best: Best3| best.elves.iter().copied().map(|elf| elf.total).sum())
 }
@@ -2,7 +2,7 @@ use std::fmt::Display;
 
 use nom::{
     branch::alt,
-    character::complete::{digit1, line_ending, multispace0},
+    character::complete::{line_ending, multispace0},
     combinator::eof,
     IResult, Parser,
 };
@@ -14,7 +14,10 @@ use nom_supreme::{
     ParserExt,
 };
 
-use crate::{express, library::Definitely};
+use crate::{
+    express,
+    library::{parse::signed, Definitely},
+};
 
 #[derive(Debug, Clone, Copy)]
 enum Command {
@@ -25,10 +28,7 @@ enum Command {
 fn parse_command(input: &str) -> IResult<&str, Command, ErrorTree<&str>> {
     alt((
         tag("noop").value(Command::Noop),
-        digit1
-            .opt_preceded_by(tag("-"))
-            .recognize()
-            .parse_from_str_cut()
+        signed()
             .map(Command::Addx)
             .context("value")
             .cut()
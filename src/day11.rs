@@ -1,28 +1,30 @@
 use std::{
     collections::{BTreeMap, HashMap},
-    error::Error,
     mem,
-    str::FromStr,
 };
 
 use anyhow::Context;
 use lazy_format::lazy_format;
 use nom::{
     branch::alt,
-    character::complete::{digit1, line_ending, multispace0, space0, space1},
-    combinator::eof,
-    sequence::tuple,
+    character::complete::{line_ending, space0, space1},
     IResult, Parser,
 };
 use nom_supreme::{
     error::ErrorTree,
     final_parser::{final_parser, Location},
-    multi::{collect_separated_terminated, parse_separated_terminated},
+    multi::collect_separated_terminated,
     tag::complete::tag,
     ParserExt,
 };
 
-use crate::{library::Counter, parser};
+use crate::{
+    library::{
+        parse::{blocks, unsigned},
+        Counter,
+    },
+    parser,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Item(i128);
@@ -94,16 +96,9 @@ struct MonkeySpec {
     preference: ThrowPreference,
 }
 
-fn parse_number<T: FromStr>(input: &str) -> IResult<&str, T, ErrorTree<&str>>
-where
-    T::Err: Error + Send + Sync + 'static,
-{
-    digit1.parse_from_str_cut().parse(input)
-}
-
 fn parse_item_set(input: &str) -> IResult<&str, Vec<Item>, ErrorTree<&str>> {
     collect_separated_terminated(
-        parse_number.map(Item).context("item"),
+        unsigned().map(Item).context("item"),
         tag(",").delimited_by(space0),
         line_ending.peek(),
     )
@@ -118,8 +113,7 @@ fn parse_monkey_line<'a, T>(
         space1.context("line indent"),
         tag(prefix).context("line prefix"),
         tag(":").delimited_by(space0),
-        payload => value,
-        line_ending;
+        payload => value;
         value
     }
 }
@@ -127,7 +121,7 @@ fn parse_monkey_line<'a, T>(
 fn parse_operand(input: &str) -> IResult<&str, Operand, ErrorTree<&str>> {
     alt((
         tag("old").value(Operand::Input),
-        parse_number.map(Operand::Literal),
+        unsigned().map(Operand::Literal),
     ))
     .parse(input)
 }
@@ -151,58 +145,59 @@ fn parse_operation(input: &str) -> IResult<&str, Operation, ErrorTree<&str>> {
 }
 
 fn parse_throw(input: &str) -> IResult<&str, MonkeyId, ErrorTree<&str>> {
-    parse_number
+    unsigned()
         .map(MonkeyId)
         .preceded_by(tag("throw to monkey "))
         .parse(input)
 }
 
 fn parse_monkey(input: &str) -> IResult<&str, (MonkeyId, MonkeySpec, Vec<Item>), ErrorTree<&str>> {
-    tuple((
+    parser! {
         // Monkey N:
-        parse_number
-            .map(MonkeyId)
-            .context("id")
-            .terminated(tag(":"))
-            .terminated(line_ending)
-            .preceded_by(tag("Monkey "))
-            .context("header"),
+        tag("Monkey "),
+        "id": unsigned().map(MonkeyId) => id,
+        tag(":"),
+        line_ending,
+        cut,
+
         // Starting items: 1, 2, 3
-        parse_monkey_line("Starting items", parse_item_set.context("item set"))
-            .context("item line"),
+        "item line": parse_monkey_line(
+            "Starting items",
+            parse_item_set.context("item set"),
+        ) => items,
+        line_ending,
+
         // Operation: new = old + s
-        parse_monkey_line(
+        "operation line": parse_monkey_line(
             "Operation",
-            parse_operation
-                .context("operation")
-                .preceded_by(tag("new = ")),
-        )
-        .context("operation line"),
+            parse_operation.context("operation").preceded_by(tag("new = ")),
+        ) => operation,
+        line_ending,
+
         // Test: divisible by 10
-        parse_monkey_line(
+        "test line": parse_monkey_line(
             "Test",
-            parse_number
-                .context("test divisor")
-                .preceded_by(tag("divisible by ")),
-        )
-        .map(|divisor| DivisibilityTest { divisor })
-        .context("test line"),
+            unsigned().context("test divisor").preceded_by(tag("divisible by ")),
+        ) => divisor,
+        line_ending,
+
         // If true: throw to monkey N
-        parse_monkey_line("If true", parse_throw).context("if true line"),
+        "if true line": parse_monkey_line("If true", parse_throw) => if_true,
+        line_ending,
+
         // If false: throw to monkey N
-        parse_monkey_line("If false", parse_throw).context("if false line"),
-    ))
-    .map(|(id, items, operation, test, if_true, if_false)| {
+        "if false line": parse_monkey_line("If false", parse_throw) => if_false;
+
         (
             id,
             MonkeySpec {
                 operation,
-                test,
+                test: DivisibilityTest { divisor },
                 preference: ThrowPreference { if_true, if_false },
             },
             items,
         )
-    })
+    }
     .parse(input)
 }
 
@@ -212,19 +207,17 @@ pub struct Input {
     collections: HashMap<MonkeyId, Vec<Item>>,
 }
 
+impl Extend<(MonkeyId, MonkeySpec, Vec<Item>)> for Input {
+    fn extend<I: IntoIterator<Item = (MonkeyId, MonkeySpec, Vec<Item>)>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|(id, spec, items)| {
+            self.specs.insert(id, spec);
+            self.collections.insert(id, items);
+        })
+    }
+}
+
 fn parse_input(input: &str) -> IResult<&str, Input, ErrorTree<&str>> {
-    parse_separated_terminated(
-        parse_monkey.context("monkey"),
-        line_ending,
-        multispace0.terminated(eof),
-        Input::default,
-        |mut input, (id, spec, items)| {
-            input.specs.insert(id, spec);
-            input.collections.insert(id, items);
-            input
-        },
-    )
-    .parse(input)
+    blocks(parse_monkey.context("monkey")).parse(input)
 }
 
 impl TryFrom<&str> for Input {
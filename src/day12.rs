@@ -1,10 +1,18 @@
-use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
 
 use anyhow::Context;
 use gridly::prelude::*;
 use gridly_grids::VecGrid;
-use itertools::Itertools;
-use lazy_format::lazy_format;
+
+use crate::{
+    input::{FromPuzzleInput, PuzzleInput},
+    library::{
+        grid::parse_char_grid,
+        image::{Image, ImageOptions},
+        search::{astar, bfs, Solution},
+    },
+    Options,
+};
 
 #[derive(Debug, Copy, Clone)]
 pub enum Site {
@@ -32,90 +40,143 @@ impl Site {
     }
 }
 
-pub struct Input {
+pub struct HeightMap {
     grid: VecGrid<Site>,
     origin: Location,
+    destination: Location,
+}
+
+fn find_site(grid: &VecGrid<Site>, target: fn(Site) -> bool) -> Option<Location> {
+    grid.rows()
+        .iter()
+        .flat_map(|row| row.iter_with_locations())
+        .find(|&(_, &site)| target(site))
+        .map(|(location, _)| location)
 }
 
-impl TryFrom<&str> for Input {
+impl TryFrom<&str> for HeightMap {
     type Error = anyhow::Error;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let rows: Vec<Vec<Site>> = value
-            .lines()
-            .enumerate()
-            .map(|(row_idx, row)| {
-                row.chars()
-                    .enumerate()
-                    .map(|(col_index, cell)| {
-                        Site::new(cell).context(lazy_format!(
-                            "invalid cell {cell:?} at row {row_idx}, column {col_index}"
-                        ))
-                    })
-                    .try_collect()
-            })
-            .try_collect()?;
-
-        let grid = VecGrid::new_from_rows(rows).context("failed to build grid out of rows")?;
+        let grid = parse_char_grid(value, |cell, _| Site::new(cell))?;
 
-        let origin = grid
-            .rows()
-            .iter()
-            .flat_map(|row| row.iter_with_locations())
-            .find(|&(_, &site)| matches!(site, Site::Start))
-            .map(|(location, _)| location)
+        let origin = find_site(&grid, |site| matches!(site, Site::Start))
             .context("no start site in grid")?;
 
-        Ok(Input { grid, origin })
+        let destination =
+            find_site(&grid, |site| matches!(site, Site::End)).context("no end site in grid")?;
+
+        Ok(HeightMap {
+            grid,
+            origin,
+            destination,
+        })
     }
 }
 
-fn count_steps(
-    input: Input,
-    build_initial_frontier: impl FnOnce(&Input) -> HashMap<Location, Site>,
-) -> anyhow::Result<usize> {
-    let mut seen = HashSet::new();
-    let mut frontier = build_initial_frontier(&input);
-
-    for steps in 0.. {
-        seen.extend(frontier.keys().copied());
-        let mut new_frontier = HashMap::with_capacity(frontier.len());
-
-        for (&loc, &site) in &frontier {
-            if matches!(site, Site::End) {
-                return Ok(steps);
-            }
-
-            for direction in EACH_DIRECTION {
-                let next_loc = loc + direction;
-                let Ok(&next_site) = input.grid.get(next_loc) else { continue };
-                if !seen.contains(&next_loc) {
-                    if next_site.height() <= site.height() + 1 {
-                        new_frontier.insert(next_loc, next_site);
-                    }
-                }
-            }
-        }
+impl HeightMap {
+    /// All the locations that can be reached in a single step from `location`
+    fn steps_from(&self, location: Location) -> impl Iterator<Item = Location> + '_ {
+        let height = self.grid.get(location).map(|site| site.height()).ok();
+
+        EACH_DIRECTION
+            .into_iter()
+            .map(move |direction| location + direction)
+            .filter(move |&next| match (height, self.grid.get(next)) {
+                (Some(height), Ok(site)) => site.height() <= height + 1,
+                _ => false,
+            })
+    }
+
+    fn is_destination(&self, location: Location) -> bool {
+        location == self.destination
+    }
+}
+
+/// Render the elevation of every site in shades of green, with the path
+/// drawn over it in red, from its start in blue to the destination in white
+fn render_path(map: &HeightMap, path: &[Location]) -> Image {
+    let mut image = Image::from_grid(&map.grid, |_, &site| {
+        let height = site.height();
+        [height * 4, 40 + height * 8, height * 4]
+    });
+
+    let root = map.grid.root();
+    let mut draw = |location: Location, color| {
+        let offset = location - root;
+        image.set(offset.rows.0 as usize, offset.columns.0 as usize, color);
+    };
+
+    path.iter()
+        .for_each(|&location| draw(location, [220, 30, 30]));
+    path.first()
+        .into_iter()
+        .for_each(|&start| draw(start, [40, 80, 255]));
+    draw(map.destination, [255, 255, 255]);
+
+    image
+}
 
-        frontier = new_frontier
+pub struct Input {
+    map: HeightMap,
+    image: ImageOptions,
+}
+
+impl FromPuzzleInput<'_> for Input {
+    fn from_puzzle_input(input: &mut PuzzleInput, options: &Options) -> anyhow::Result<Self> {
+        Ok(Self {
+            map: HeightMap::try_from(input.text()?)?,
+            image: options.image.clone(),
+        })
     }
+}
+
+impl Input {
+    fn report<C: Display>(&self, solution: Solution<Location, C>) -> anyhow::Result<C> {
+        eprintln!(
+            "Found a path through {} locations after visiting {} of {} discovered",
+            solution.path.len(),
+            solution.visited,
+            solution.discovered
+        );
 
-    anyhow::bail!("no path to end")
+        self.image.save(|| render_path(&self.map, &solution.path))?;
+
+        Ok(solution.cost)
+    }
 }
 
-pub fn part1(input: Input) -> anyhow::Result<usize> {
-    count_steps(input, |input| HashMap::from([(input.origin, Site::Start)]))
+pub fn part1(input: Input) -> anyhow::Result<isize> {
+    let map = &input.map;
+
+    // Each step costs 1, so the manhattan distance to the destination is a
+    // lower bound for the remaining cost
+    astar(
+        [map.origin],
+        |&location| map.steps_from(location).map(|next| (next, 1)),
+        |&location| (map.destination - location).manhattan_length(),
+        |&location| map.is_destination(location),
+    )
+    .context("no path to end")
+    .and_then(|solution| input.report(solution))
 }
 
 pub fn part2(input: Input) -> anyhow::Result<usize> {
-    count_steps(input, |input| {
-        input
-            .grid
-            .rows()
-            .iter()
-            .flat_map(|row| row.iter_with_locations())
-            .map(|(loc, &site)| (loc, site))
-            .filter(|&(_, site)| site.height() == 0)
-            .collect()
-    })
+    let map = &input.map;
+
+    let starts = map
+        .grid
+        .rows()
+        .iter()
+        .flat_map(|row| row.iter_with_locations())
+        .filter(|&(_, site)| site.height() == 0)
+        .map(|(location, _)| location);
+
+    bfs(
+        starts,
+        |&location| map.steps_from(location),
+        |&location| map.is_destination(location),
+    )
+    .context("no path to end")
+    .and_then(|solution| input.report(solution))
 }
@@ -3,9 +3,7 @@ use std::{cmp::Ordering, ops::ControlFlow};
 use itertools::Itertools;
 use nom::{
     branch::alt,
-    character::complete::{char, digit1, line_ending, multispace0},
-    combinator::eof,
-    sequence::tuple,
+    character::complete::{char, line_ending},
     IResult, Parser,
 };
 use nom_supreme::{
@@ -15,7 +13,10 @@ use nom_supreme::{
     ParserExt,
 };
 
-use crate::library::Definitely;
+use crate::library::{
+    parse::{blocks, unsigned},
+    Definitely,
+};
 
 #[derive(Debug, Clone)]
 enum Value {
@@ -65,10 +66,6 @@ impl Ord for Value {
     }
 }
 
-fn parse_number(input: &str) -> IResult<&str, i64, ErrorTree<&str>> {
-    digit1.parse_from_str_cut().parse(input)
-}
-
 fn parse_list(input: &str) -> IResult<&str, Vec<Value>, ErrorTree<&str>> {
     collect_separated_terminated(parse_value.context("value"), char(','), char(']'))
         .or(char(']').map(|_| Vec::new()))
@@ -79,7 +76,7 @@ fn parse_list(input: &str) -> IResult<&str, Vec<Value>, ErrorTree<&str>> {
 
 fn parse_value(input: &str) -> IResult<&str, Value, ErrorTree<&str>> {
     alt((
-        parse_number.context("number").map(Value::Number),
+        unsigned().context("number").map(Value::Number),
         parse_list.context("list").map(Value::List),
     ))
     .parse(input)
@@ -97,13 +94,9 @@ pub struct Input {
 }
 
 fn parse_input(input: &str) -> IResult<&str, Input, ErrorTree<&str>> {
-    collect_separated_terminated(
-        parse_value_pair.context("value pair"),
-        tuple((line_ending, line_ending)),
-        multispace0.terminated(eof),
-    )
-    .map(|pairs| Input { pairs })
-    .parse(input)
+    blocks(parse_value_pair.context("value pair"))
+        .map(|pairs| Input { pairs })
+        .parse(input)
 }
 
 impl TryFrom<&str> for Input {
@@ -2,7 +2,7 @@ use anyhow::Context;
 use gridly::{prelude::*, range::RangeError};
 use gridly_grids::SparseGrid;
 use nom::{
-    character::complete::{char, digit1, line_ending, multispace0},
+    character::complete::{line_ending, multispace0},
     combinator::{eof, success},
     IResult, Parser,
 };
@@ -14,22 +14,17 @@ use nom_supreme::{
     ParserExt,
 };
 
-fn parse_coordinate<T: LocationComponent>(input: &str) -> IResult<&str, T, ErrorTree<&str>> {
-    digit1
-        .opt_preceded_by(char('-'))
-        .recognize()
-        .parse_from_str_cut()
-        .map(T::from)
-        .parse(input)
-}
+use crate::{
+    input::{FromPuzzleInput, PuzzleInput},
+    library::{
+        image::{Image, ImageOptions},
+        parse::xy_location,
+    },
+    Options,
+};
 
 fn parse_coords(input: &str) -> IResult<&str, Location, ErrorTree<&str>> {
-    parse_coordinate
-        .context("x")
-        .terminated(char(','))
-        .and(parse_coordinate.context("y"))
-        .map(|(column, row)| Location { row, column })
-        .parse(input)
+    xy_location().parse(input)
 }
 
 struct Chain {
@@ -113,16 +108,77 @@ fn parse_grid(input: &str) -> IResult<&str, SparseGrid<Cell>, ErrorTree<&str>> {
     .parse(input)
 }
 
-pub struct Input {
+pub struct Cave {
     grid: SparseGrid<Cell>,
 }
 
-impl TryFrom<&str> for Input {
+impl TryFrom<&str> for Cave {
     type Error = ErrorTree<ErrorLocation>;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        final_parser(parse_grid.map(|grid| Input { grid }))(value)
+        final_parser(parse_grid.map(|grid| Cave { grid }))(value)
+    }
+}
+
+pub struct Input {
+    cave: Cave,
+    image: ImageOptions,
+}
+
+impl FromPuzzleInput<'_> for Input {
+    fn from_puzzle_input(input: &mut PuzzleInput, options: &Options) -> anyhow::Result<Self> {
+        Ok(Self {
+            cave: Cave::try_from(input.text()?)?,
+            image: options.image.clone(),
+        })
+    }
+}
+
+/// Render the rock and sand in the cave, cropped to the area they cover,
+/// along with the point the sand falls from and the floor, if there is one
+fn render_cave(grid: &SparseGrid<Cell>, sand_start: Location, floor: Option<Row>) -> Image {
+    let locations = grid
+        .occupied_entries()
+        .map(|(&location, _)| location)
+        .chain([sand_start]);
+
+    let (min, max) = locations.fold((sand_start, sand_start), |(min, max), location| {
+        (
+            Location::new(min.row.min(location.row), min.column.min(location.column)),
+            Location::new(max.row.max(location.row), max.column.max(location.column)),
+        )
+    });
+
+    let floor = floor.map(|floor| floor + Rows(1));
+    let bottom = floor.map_or(max.row, |floor| floor.max(max.row));
+    let size = Location::new(bottom, max.column) - min;
+
+    let mut image = Image::new(
+        size.columns.0 as usize + 1,
+        size.rows.0 as usize + 1,
+        [20, 20, 28],
+    );
+
+    let mut draw = |location: Location, color| {
+        let offset = location - min;
+        image.set(offset.rows.0 as usize, offset.columns.0 as usize, color);
+    };
+
+    grid.occupied_entries()
+        .for_each(|(&location, &cell)| match cell {
+            Cell::Empty => {}
+            Cell::Rock => draw(location, [120, 120, 120]),
+            Cell::Sand => draw(location, [230, 190, 110]),
+        });
+
+    if let Some(floor) = floor {
+        (min.column.0..=max.column.0)
+            .for_each(|column| draw(floor + Column(column), [120, 120, 120]));
     }
+
+    draw(sand_start, [220, 30, 30]);
+
+    image
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -132,7 +188,7 @@ enum SearchResult {
 }
 
 pub fn part1(input: Input) -> anyhow::Result<usize> {
-    let mut grid = input.grid;
+    let mut grid = input.cave.grid;
     let sand_start = Column(500) + Row(0);
 
     // Each iteration of this loop is the entire journey for one piece of sand
@@ -153,10 +209,12 @@ pub fn part1(input: Input) -> anyhow::Result<usize> {
                 }) {
                 Some(SearchResult::Available(new_sand)) => sand = new_sand,
                 Some(SearchResult::Void(_)) => {
+                    input.image.save(|| render_cave(&grid, sand_start, None))?;
+
                     return Ok(grid
                         .occupied_entries()
                         .filter(|&(_, &cell)| matches!(cell, Cell::Sand))
-                        .count())
+                        .count());
                 }
                 None => break sand,
             }
@@ -171,7 +229,7 @@ pub fn part1(input: Input) -> anyhow::Result<usize> {
 }
 
 pub fn part2(input: Input) -> anyhow::Result<usize> {
-    let mut grid = input.grid;
+    let mut grid = input.cave.grid;
     // The actual floor is 1 row below this; this is the location where sand
     // will come to rest
     let floor = grid.outer_bound().row;
@@ -202,6 +260,10 @@ pub fn part2(input: Input) -> anyhow::Result<usize> {
                 None => {
                     grid.insert(sand, Cell::Sand);
                     if sand == sand_start {
+                        input
+                            .image
+                            .save(|| render_cave(&grid, sand_start, Some(floor)))?;
+
                         return Ok(grid
                             .occupied_entries()
                             .filter(|&(_, &cell)| matches!(cell, Cell::Sand))
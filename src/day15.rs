@@ -1,43 +1,21 @@
 use anyhow::Context;
 use gridly::prelude::*;
-use nom::{
-    character::complete::{char, digit1, line_ending, multispace0},
-    combinator::eof,
-    IResult, Parser,
-};
-use nom_supreme::{
-    error::ErrorTree, final_parser::final_parser, multi::collect_separated_terminated,
-    tag::complete::tag, ParserExt,
-};
+use nom::{IResult, Parser};
+use nom_supreme::{error::ErrorTree, final_parser::final_parser, tag::complete::tag, ParserExt};
 use rayon::prelude::*;
 
-use crate::parser;
+use crate::{
+    library::{
+        interval::{Interval, IntervalSet},
+        parse::{lines, xy_location},
+    },
+    parser,
+};
 
 pub struct Input {
     signals: Vec<Signal>,
 }
 
-fn parse_component<'a, T: LocationComponent>(
-    prefix: &'static str,
-) -> impl Parser<&'a str, T, ErrorTree<&'a str>> {
-    digit1
-        .opt_preceded_by(char('-'))
-        .recognize()
-        .parse_from_str_cut()
-        .map(|coord: isize| T::from(coord))
-        .preceded_by(char('='))
-        .preceded_by(tag(prefix))
-}
-
-fn parse_location(input: &str) -> IResult<&str, Location, ErrorTree<&str>> {
-    parse_component("x")
-        .context("column")
-        .terminated(tag(", "))
-        .and(parse_component("y").context("row"))
-        .map(|(column, row)| Location { row, column })
-        .parse(input)
-}
-
 #[derive(Debug, Clone, Copy)]
 struct Signal {
     sensor: Location,
@@ -48,26 +26,28 @@ impl Signal {
     fn radius(&self) -> isize {
         (self.beacon - self.sensor).manhattan_length()
     }
+
+    /// The interval of columns in `row` that are within range of this sensor
+    fn coverage(&self, row: Row) -> Option<Interval<isize>> {
+        let reach = self.radius() - (row - self.sensor.row).0.abs();
+
+        Interval::new(self.sensor.column.0 - reach, self.sensor.column.0 + reach)
+    }
 }
 
 fn parse_signal(input: &str) -> IResult<&str, Signal, ErrorTree<&str>> {
     parser! {
         tag("Sensor at "),
-        parse_location.context("sensor") => sensor,
+        "sensor": xy_location() => sensor,
         tag(": closest beacon is at "),
-        parse_location.context("beacon") => beacon;
+        "beacon": xy_location() => beacon;
         Signal { sensor, beacon }
     }
     .parse(input)
 }
 
 fn parse_signals(input: &str) -> IResult<&str, Vec<Signal>, ErrorTree<&str>> {
-    collect_separated_terminated(
-        parse_signal.context("signal"),
-        line_ending,
-        multispace0.terminated(eof),
-    )
-    .parse(input)
+    lines(parse_signal.context("signal")).parse(input)
 }
 
 impl TryFrom<&str> for Input {
@@ -78,53 +58,24 @@ impl TryFrom<&str> for Input {
     }
 }
 
-pub fn part1(input: Input) -> anyhow::Result<usize> {
-    // Need to determine our scanning distance. Find the leftmost and rightmost
-    // sensors and add their respective radii.
-    let start: Column = input
-        .signals
-        .iter()
-        .map(|signal| signal.sensor.column)
-        .min()
-        .context("no signals in the input")?;
+pub fn part1(input: Input) -> anyhow::Result<isize> {
+    let row = Row(2000000);
 
-    let end: Column = input
+    let coverage: IntervalSet<isize> = input
         .signals
         .iter()
-        .map(|signal| signal.sensor.column)
-        .max()
-        .context("no signals in the input")?;
+        .filter_map(|signal| signal.coverage(row))
+        .collect();
 
-    let radius = input
+    // Locations with a known beacon can obviously contain a beacon
+    let beacons: IntervalSet<isize> = input
         .signals
         .iter()
-        .map(|signal| signal.radius())
-        .max()
-        .context("no signals in the input")?;
-
-    // To prevent tiny off by one errors, add a small buffer to both sides
-    let start = start - Columns(radius + 10);
-    let end = end + Columns(radius + 10);
-
-    eprintln!("Identified start and end: {start:?} .. {end:?}");
-
-    let row = Row(2000000);
-
-    // TODO: this is embarrassingly parallel, get rayon in here to help out
-    let in_range_count = (start.0..end.0)
-        .into_par_iter()
-        .map(|column| Column(column))
-        .map(|column| column + row)
-        .filter(|&location| {
-            input
-                .signals
-                .iter()
-                .any(|signal| (location - signal.sensor).manhattan_length() <= signal.radius())
-        })
-        .filter(|&location| input.signals.iter().all(|signal| signal.beacon != location))
-        .count();
+        .filter(|signal| signal.beacon.row == row)
+        .map(|signal| Interval::point(signal.beacon.column.0))
+        .collect();
 
-    Ok(in_range_count)
+    Ok(coverage.subtract(&beacons).len())
 }
 
 pub fn part2(input: Input) -> anyhow::Result<isize> {
@@ -1,5 +1,16 @@
-use anyhow::Context;
-use nom::{branch::alt, combinator::eof, IResult, Parser};
+use std::{collections::HashSet, fmt::Debug, fs, path::Path};
+
+use anyhow::{bail, ensure, Context};
+use itertools::Itertools;
+use joinery::JoinableIterator;
+use nom::{
+    branch::alt,
+    bytes::complete::is_not,
+    character::complete::space1,
+    combinator::eof,
+    error::{ErrorKind, FromExternalError},
+    IResult, Parser,
+};
 use nom_supreme::{
     error::ErrorTree,
     final_parser::{final_parser, Location},
@@ -8,64 +19,429 @@ use nom_supreme::{
     ParserExt as _,
 };
 
+use crate::{
+    express,
+    input::{FromPuzzleInput, PuzzleInput},
+    library::parse::signed,
+    Options,
+};
+
+/// The rules of the original puzzle
+const CLASSIC: &str = "
+sign Rock A X 1
+sign Paper B Y 2
+sign Scissors C Z 3
+
+Rock beats Scissors
+Paper beats Rock
+Scissors beats Paper
+
+lose 0 X
+draw 3 Y
+win 6 Z
+";
+
+/// Rock-Paper-Scissors-Lizard-Spock. The opponent plays A through E, and we
+/// play V through Z.
+const RPSLS: &str = "
+sign Rock A V 1
+sign Paper B W 2
+sign Scissors C X 3
+sign Lizard D Y 4
+sign Spock E Z 5
+
+Scissors beats Paper
+Paper beats Rock
+Rock beats Lizard
+Lizard beats Spock
+Spock beats Scissors
+Scissors beats Lizard
+Lizard beats Paper
+Paper beats Spock
+Spock beats Rock
+Rock beats Scissors
+
+lose 0 X
+draw 3 Y
+win 6 Z
+";
+
+/// A built in set of game rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Preset {
+    /// Rock-Paper-Scissors, as in the original puzzle
+    Classic,
+
+    /// Rock-Paper-Scissors-Lizard-Spock
+    Rpsls,
+}
+
 #[derive(Debug, Clone, Copy)]
 enum Player {
     Opponent,
     Me,
 }
 
+/// A sign in the game, identified by its position in the rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Sign(usize);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Sign {
-    Rock,
-    Paper,
-    Scissors,
-}
-
-impl Sign {
-    pub fn wins_against(self) -> Sign {
-        match self {
-            Rock => Scissors,
-            Paper => Rock,
-            Scissors => Paper,
-        }
+enum Outcome {
+    Win,
+    Draw,
+    Lose,
+}
+
+use Outcome::*;
+
+/// Strategy guide symbols, mapped to the values they stand for. Each symbol
+/// is a single word.
+#[derive(Debug, Clone)]
+struct SymbolTable<T> {
+    symbols: Vec<(String, T)>,
+}
+
+impl<T: Copy> SymbolTable<T> {
+    fn get(&self, symbol: &str) -> Option<T> {
+        self.symbols
+            .iter()
+            .find(|(candidate, _)| candidate == symbol)
+            .map(|&(_, value)| value)
     }
 
-    pub fn loses_against(self) -> Sign {
-        match self {
-            Rock => Paper,
-            Paper => Scissors,
-            Scissors => Rock,
+    /// Create a parser that parses any of the symbols in this table
+    fn parser<'i>(&self) -> impl Parser<&'i str, T, ErrorTree<&'i str>> + '_ {
+        move |input: &'i str| {
+            let (tail, symbol) = parse_word(input)?;
+
+            match self.get(symbol) {
+                Some(value) => Ok((tail, value)),
+                None => Err(nom::Err::Error(ErrorTree::from_external_error(
+                    input,
+                    ErrorKind::Tag,
+                    anyhow::anyhow!(
+                        "unknown symbol {symbol:?}; expected one of {:?}",
+                        self.symbols
+                            .iter()
+                            .map(|(symbol, _)| symbol.as_str())
+                            .collect::<Vec<_>>()
+                    ),
+                ))),
+            }
         }
     }
 }
 
-use Sign::*;
+fn parse_word(input: &str) -> IResult<&str, &str, ErrorTree<&str>> {
+    is_not(" \t\r\n").parse(input)
+}
+
+#[derive(Debug, Clone)]
+struct SignRule {
+    name: String,
+    score: i64,
+}
+
+/// The definition of an n-sign cyclic game: the signs, which signs beat which,
+/// the score for each sign and outcome, and the symbols used by the strategy
+/// guide.
+#[derive(Debug, Clone)]
+struct GameRules {
+    signs: Vec<SignRule>,
+
+    /// Pairs of (winner, loser)
+    beats: HashSet<(Sign, Sign)>,
+
+    opponent_symbols: SymbolTable<Sign>,
+    my_symbols: SymbolTable<Sign>,
+    outcome_symbols: SymbolTable<Outcome>,
 
-use crate::express;
+    win: i64,
+    draw: i64,
+    lose: i64,
+}
+
+#[derive(Debug, Clone)]
+enum RulesLine<'a> {
+    Sign {
+        name: &'a str,
+        opponent: &'a str,
+        me: &'a str,
+        score: i64,
+    },
+    Beats {
+        winner: &'a str,
+        loser: &'a str,
+    },
+    Outcome {
+        outcome: Outcome,
+        score: i64,
+        symbol: &'a str,
+    },
+}
 
-fn generic_rule<'a, T>(rules: [(&'static str, T); 3]) -> impl Parser<&'a str, T, ErrorTree<&'a str>>
-where
-    T: Copy,
-{
+fn parse_rules_line(input: &str) -> IResult<&str, RulesLine<'_>, ErrorTree<&str>> {
     alt((
-        tag(rules[0].0).value(rules[0].1),
-        tag(rules[1].0).value(rules[1].1),
-        tag(rules[2].0).value(rules[2].1),
+        tag("sign")
+            .precedes(parse_word.context("name").preceded_by(space1))
+            .and(parse_word.context("opponent symbol").preceded_by(space1))
+            .and(parse_word.context("own symbol").preceded_by(space1))
+            .and(signed().context("score").preceded_by(space1))
+            .map(|(((name, opponent), me), score)| RulesLine::Sign {
+                name,
+                opponent,
+                me,
+                score,
+            }),
+        alt((
+            tag("win").value(Win),
+            tag("draw").value(Draw),
+            tag("lose").value(Lose),
+        ))
+        .and(signed().context("score").preceded_by(space1))
+        .and(parse_word.context("symbol").preceded_by(space1))
+        .map(|((outcome, score), symbol)| RulesLine::Outcome {
+            outcome,
+            score,
+            symbol,
+        }),
+        parse_word
+            .context("winner")
+            .terminated(tag(" beats "))
+            .and(parse_word.context("loser"))
+            .map(|(winner, loser)| RulesLine::Beats { winner, loser }),
     ))
+    .parse(input)
 }
 
-fn sign_rule<'a>(
-    [rock, paper, scissors]: [&'static str; 3],
-) -> impl Parser<&'a str, Sign, ErrorTree<&'a str>> {
-    generic_rule([(rock, Rock), (paper, Paper), (scissors, Scissors)])
-}
+impl GameRules {
+    /// Parse a set of game rules. Each line is one of:
+    ///
+    /// - `sign <name> <opponent symbol> <own symbol> <score>`
+    /// - `<name> beats <name>`
+    /// - `win|draw|lose <score> <symbol>`
+    ///
+    /// Blank lines and lines starting with `#` are ignored. Every pair of
+    /// signs must have exactly one winner, and every sign must beat and lose
+    /// to at least one other sign.
+    fn parse(input: &str) -> anyhow::Result<Self> {
+        let mut signs: Vec<SignRule> = Vec::new();
+        let mut opponent_symbols = Vec::new();
+        let mut my_symbols = Vec::new();
+        let mut beats = Vec::new();
+        let mut outcomes = Vec::new();
+
+        for (line_idx, line) in input.lines().enumerate() {
+            let line_number = line_idx + 1;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parsed: Result<RulesLine, ErrorTree<Location>> =
+                final_parser(parse_rules_line)(line);
+            let parsed =
+                parsed.with_context(|| format!("failed to parse rules on line {line_number}"))?;
+
+            match parsed {
+                RulesLine::Sign {
+                    name,
+                    opponent,
+                    me,
+                    score,
+                } => {
+                    ensure!(
+                        signs.iter().all(|sign| sign.name != name),
+                        "line {line_number}: sign {name:?} is defined more than once"
+                    );
+
+                    let sign = Sign(signs.len());
+                    signs.push(SignRule {
+                        name: name.to_owned(),
+                        score,
+                    });
+                    opponent_symbols.push((opponent.to_owned(), sign));
+                    my_symbols.push((me.to_owned(), sign));
+                }
+                RulesLine::Beats { winner, loser } => beats.push((line_number, winner, loser)),
+                RulesLine::Outcome {
+                    outcome,
+                    score,
+                    symbol,
+                } => outcomes.push((line_number, outcome, score, symbol.to_owned())),
+            }
+        }
+
+        let find_sign = |line_number: usize, name: &str| {
+            signs
+                .iter()
+                .position(|sign| sign.name == name)
+                .map(Sign)
+                .with_context(|| format!("line {line_number}: no such sign {name:?}"))
+        };
+
+        let beats = beats
+            .into_iter()
+            .map(|(line_number, winner, loser)| {
+                let pair = (
+                    find_sign(line_number, winner)?,
+                    find_sign(line_number, loser)?,
+                );
+                ensure!(
+                    pair.0 != pair.1,
+                    "line {line_number}: {winner:?} can't beat itself"
+                );
+                Ok(pair)
+            })
+            .collect::<anyhow::Result<HashSet<_>>>()?;
+
+        for (a, a_rule) in signs.iter().enumerate() {
+            let beats_any = (0..signs.len()).any(|b| beats.contains(&(Sign(a), Sign(b))));
+            let loses_any = (0..signs.len()).any(|b| beats.contains(&(Sign(b), Sign(a))));
+
+            ensure!(beats_any, "{:?} doesn't beat any other sign", a_rule.name);
+            ensure!(
+                loses_any,
+                "{:?} doesn't lose to any other sign",
+                a_rule.name
+            );
+
+            for (b, b_rule) in signs.iter().enumerate().skip(a + 1) {
+                match (
+                    beats.contains(&(Sign(a), Sign(b))),
+                    beats.contains(&(Sign(b), Sign(a))),
+                ) {
+                    (true, false) | (false, true) => {}
+                    (false, false) => bail!(
+                        "there's no winner between {:?} and {:?}",
+                        a_rule.name,
+                        b_rule.name
+                    ),
+                    (true, true) => bail!(
+                        "{:?} and {:?} both beat each other",
+                        a_rule.name,
+                        b_rule.name
+                    ),
+                }
+            }
+        }
+
+        let outcome_score = |outcome: Outcome| {
+            let mut matching = outcomes.iter().filter(|&&(_, o, _, _)| o == outcome);
+
+            match (matching.next(), matching.next()) {
+                (None, _) => bail!("no score given for {outcome:?}"),
+                (Some(_), Some(&(line_number, ..))) => {
+                    bail!("line {line_number}: score for {outcome:?} is given more than once")
+                }
+                (Some(&(_, _, score, _)), None) => Ok(score),
+            }
+        };
+
+        let rules = Self {
+            win: outcome_score(Win)?,
+            draw: outcome_score(Draw)?,
+            lose: outcome_score(Lose)?,
+            outcome_symbols: SymbolTable {
+                symbols: outcomes
+                    .iter()
+                    .map(|(_, outcome, _, symbol)| (symbol.clone(), *outcome))
+                    .collect(),
+            },
+            opponent_symbols: SymbolTable {
+                symbols: opponent_symbols,
+            },
+            my_symbols: SymbolTable {
+                symbols: my_symbols,
+            },
+            beats,
+            signs,
+        };
 
-fn parse_opponent(input: &str) -> IResult<&str, Sign, ErrorTree<&str>> {
-    sign_rule(["A", "B", "C"]).parse(input)
+        for (role, table) in [
+            ("opponent", rules.opponent_symbols.symbols.as_slice()),
+            ("own", rules.my_symbols.symbols.as_slice()),
+        ] {
+            ensure_unique_symbols(role, table.iter().map(|(symbol, _)| symbol))?;
+        }
+
+        ensure_unique_symbols(
+            "outcome",
+            rules
+                .outcome_symbols
+                .symbols
+                .iter()
+                .map(|(symbol, _)| symbol),
+        )?;
+
+        Ok(rules)
+    }
+
+    fn preset(preset: Preset) -> Self {
+        Self::parse(match preset {
+            Preset::Classic => CLASSIC,
+            Preset::Rpsls => RPSLS,
+        })
+        .expect("built in rules should be valid")
+    }
+
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read rules file {:?}", path.display()))?;
+
+        Self::parse(&content).with_context(|| format!("invalid rules file {:?}", path.display()))
+    }
+
+    fn beats(&self, winner: Sign, loser: Sign) -> bool {
+        self.beats.contains(&(winner, loser))
+    }
+
+    fn sign_score(&self, sign: Sign) -> i64 {
+        self.signs[sign.0].score
+    }
+
+    fn outcome_score(&self, outcome: Outcome) -> i64 {
+        match outcome {
+            Win => self.win,
+            Draw => self.draw,
+            Lose => self.lose,
+        }
+    }
+
+    /// Pick the sign to play against `opponent` to get `outcome`. If there
+    /// are several, pick the highest scoring one (the earliest, in case of a
+    /// tie).
+    fn choose(&self, opponent: Sign, outcome: Outcome) -> Sign {
+        let candidates = (0..self.signs.len())
+            .map(Sign)
+            .filter(|&sign| match outcome {
+                Draw => sign == opponent,
+                Win => self.beats(sign, opponent),
+                Lose => self.beats(opponent, sign),
+            });
+
+        candidates
+            .rev()
+            .max_by_key(|&sign| self.sign_score(sign))
+            .expect("every sign beats and loses to at least one other sign")
+    }
 }
 
-fn parse_me(input: &str) -> IResult<&str, Sign, ErrorTree<&str>> {
-    sign_rule(["X", "Y", "Z"]).parse(input)
+fn ensure_unique_symbols<'a>(
+    role: &str,
+    symbols: impl IntoIterator<Item = &'a String>,
+) -> anyhow::Result<()> {
+    let mut seen = HashSet::new();
+
+    symbols.into_iter().try_for_each(|symbol| {
+        ensure!(
+            seen.insert(symbol),
+            "{role} symbol {symbol:?} is used more than once"
+        );
+        Ok(())
+    })
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -74,20 +450,24 @@ struct Match {
     me: Sign,
 }
 
-fn parse_match(input: &str) -> IResult<&str, Match, ErrorTree<&str>> {
-    parse_opponent
+fn parse_match<'a>(
+    rules: &'a GameRules,
+    my_symbols: &'a SymbolTable<Sign>,
+) -> impl Parser<&'a str, Match, ErrorTree<&'a str>> {
+    rules
+        .opponent_symbols
+        .parser()
         .context("opponent sign")
         .terminated(tag(" "))
-        .and(parse_me.context("own sign"))
+        .and(my_symbols.parser().context("own sign"))
         .map(|(opponent, me)| Match { opponent, me })
-        .parse(input)
 }
 
 impl Match {
-    fn play(&self) -> Option<Player> {
-        if self.me.wins_against() == self.opponent {
+    fn play(&self, rules: &GameRules) -> Option<Player> {
+        if rules.beats(self.me, self.opponent) {
             Some(Player::Me)
-        } else if self.opponent.wins_against() == self.me {
+        } else if rules.beats(self.opponent, self.me) {
             Some(Player::Opponent)
         } else {
             None
@@ -96,10 +476,11 @@ impl Match {
 }
 
 trait Evaluator: Default {
-    fn add_match(&mut self, game: Match);
+    fn add_match(&mut self, rules: &GameRules, game: Match);
 }
 
 fn parse_matches<'a, T: Evaluator>(
+    rules: &'a GameRules,
     parse_match: impl Parser<&'a str, Match, ErrorTree<&'a str>>,
 ) -> impl Parser<&'a str, T, ErrorTree<&'a str>> {
     parse_separated_terminated(
@@ -107,12 +488,19 @@ fn parse_matches<'a, T: Evaluator>(
         tag("\n"),
         eof,
         T::default,
-        |evaluator, game| express!(evaluator.add_match(game)),
+        |evaluator, game| express!(evaluator.add_match(rules, game)),
     )
 }
 
-fn final_parse_matches<T: Evaluator>(input: &str) -> Result<T, ErrorTree<Location>> {
-    final_parser(parse_matches(parse_match.context("match")))(input.trim())
+fn final_parse_matches<'a, T: Evaluator>(
+    rules: &'a GameRules,
+    my_symbols: &'a SymbolTable<Sign>,
+    input: &'a str,
+) -> Result<T, ErrorTree<Location>> {
+    final_parser(parse_matches(
+        rules,
+        parse_match(rules, my_symbols).context("match"),
+    ))(input.trim())
 }
 
 #[derive(Debug, Default)]
@@ -121,62 +509,155 @@ struct TotalScore {
 }
 
 impl Evaluator for TotalScore {
-    fn add_match(&mut self, game: Match) {
-        self.score += match game.me {
-            Rock => 1,
-            Paper => 2,
-            Scissors => 3,
-        };
+    fn add_match(&mut self, rules: &GameRules, game: Match) {
+        self.score += rules.sign_score(game.me);
 
-        self.score += match game.play() {
-            Some(Player::Opponent) => 0,
-            None => 3,
-            Some(Player::Me) => 6,
-        };
+        self.score += rules.outcome_score(match game.play(rules) {
+            Some(Player::Opponent) => Lose,
+            None => Draw,
+            Some(Player::Me) => Win,
+        });
     }
 }
 
-pub fn part1(input: &str) -> anyhow::Result<i64> {
-    final_parse_matches(input)
-        .context("failed to parse input")
-        .map(|outcome: TotalScore| outcome.score)
+/// Score the strategy guide under every way of assigning distinct `values` to
+/// the symbols in `table`, returning each assignment with its total score.
+fn decode<T: Copy>(
+    table: &SymbolTable<T>,
+    values: &[T],
+    mut evaluate: impl FnMut(&SymbolTable<T>) -> Result<TotalScore, ErrorTree<Location>>,
+) -> anyhow::Result<Vec<(SymbolTable<T>, i64)>> {
+    ensure!(
+        table.symbols.len() <= values.len(),
+        "there are more symbols than things they could mean"
+    );
+
+    values
+        .iter()
+        .copied()
+        .permutations(table.symbols.len())
+        .map(|assignment| {
+            let candidate = SymbolTable {
+                symbols: table
+                    .symbols
+                    .iter()
+                    .map(|(symbol, _)| symbol.clone())
+                    .zip(assignment)
+                    .collect(),
+            };
+
+            let score = evaluate(&candidate).context("failed to parse input")?.score;
+
+            Ok((candidate, score))
+        })
+        .collect()
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Outcome {
-    Win,
-    Draw,
-    Lose,
+/// Print every decoding and its score to stderr, followed by the best and
+/// worst decodings.
+fn print_decodings<T: Copy>(decodings: &[(SymbolTable<T>, i64)], name: impl Fn(T) -> String) {
+    let describe = |table: &SymbolTable<T>| {
+        table
+            .symbols
+            .iter()
+            .map(|(symbol, value)| format!("{symbol}={}", name(*value)))
+            .join_with(" ")
+            .to_string()
+    };
+
+    for (table, score) in decodings {
+        eprintln!("{}: {score}", describe(table));
+    }
+
+    // Prefer the earliest of several equally good (or bad) decodings
+    let best = decodings.iter().rev().max_by_key(|&&(_, score)| score);
+    let worst = decodings.iter().min_by_key(|&&(_, score)| score);
+
+    if let (Some((best, best_score)), Some((worst, worst_score))) = (best, worst) {
+        eprintln!("best: {} ({best_score})", describe(best));
+        eprintln!("worst: {} ({worst_score})", describe(worst));
+    }
 }
 
-use Outcome::*;
+pub struct Input<'a> {
+    text: &'a str,
+    rules: GameRules,
+    decode: bool,
+}
 
-fn parse_outcome(input: &str) -> IResult<&str, Outcome, ErrorTree<&str>> {
-    generic_rule([("X", Lose), ("Y", Draw), ("Z", Win)]).parse(input)
+impl<'a> FromPuzzleInput<'a> for Input<'a> {
+    fn from_puzzle_input(input: &'a mut PuzzleInput, options: &'a Options) -> anyhow::Result<Self> {
+        let rules = match options.rules {
+            Some(ref path) => GameRules::load(path)?,
+            None => GameRules::preset(options.preset),
+        };
+
+        Ok(Self {
+            text: input.text()?,
+            rules,
+            decode: options.decode,
+        })
+    }
+}
+
+pub fn part1(input: Input) -> anyhow::Result<i64> {
+    let rules = &input.rules;
+
+    if input.decode {
+        let signs: Vec<Sign> = (0..rules.signs.len()).map(Sign).collect();
+        let decodings = decode(&rules.my_symbols, &signs, |my_symbols| {
+            final_parse_matches(rules, my_symbols, input.text)
+        })?;
+
+        print_decodings(&decodings, |sign| rules.signs[sign.0].name.clone());
+    }
+
+    final_parse_matches(rules, &rules.my_symbols, input.text)
+        .context("failed to parse input")
+        .map(|outcome: TotalScore| outcome.score)
 }
 
-fn parse_match_v2(input: &str) -> IResult<&str, Match, ErrorTree<&str>> {
-    parse_opponent
+fn parse_match_v2<'a>(
+    rules: &'a GameRules,
+    outcome_symbols: &'a SymbolTable<Outcome>,
+) -> impl Parser<&'a str, Match, ErrorTree<&'a str>> {
+    rules
+        .opponent_symbols
+        .parser()
         .context("opponent sign")
         .terminated(tag(" "))
-        .and(parse_outcome.context("predetermined outcome"))
+        .and(outcome_symbols.parser().context("predetermined outcome"))
         .map(|(opponent, outcome)| Match {
             opponent,
-            me: match outcome {
-                Draw => opponent,
-                Win => opponent.loses_against(),
-                Lose => opponent.wins_against(),
-            },
+            me: rules.choose(opponent, outcome),
         })
-        .parse(input)
 }
 
-fn final_parse_matches_v2<T: Evaluator>(input: &str) -> Result<T, ErrorTree<Location>> {
-    final_parser(parse_matches(parse_match_v2.context("match")))(input.trim())
+fn final_parse_matches_v2<'a, T: Evaluator>(
+    rules: &'a GameRules,
+    outcome_symbols: &'a SymbolTable<Outcome>,
+    input: &'a str,
+) -> Result<T, ErrorTree<Location>> {
+    final_parser(parse_matches(
+        rules,
+        parse_match_v2(rules, outcome_symbols).context("match"),
+    ))(input.trim())
 }
 
-pub fn part2(input: &str) -> anyhow::Result<i64> {
-    final_parse_matches_v2(input)
+pub fn part2(input: Input) -> anyhow::Result<i64> {
+    let rules = &input.rules;
+
+    if input.decode {
+        let decodings = decode(
+            &rules.outcome_symbols,
+            &[Win, Draw, Lose],
+            |outcome_symbols| final_parse_matches_v2(rules, outcome_symbols, input.text),
+        )?;
+
+        print_decodings(&decodings, |outcome| format!("{outcome:?}"));
+    }
+
+    final_parse_matches_v2(rules, &rules.outcome_symbols, input.text)
         .context("failed to parse input")
         .map(|outcome: TotalScore| outcome.score)
 }
@@ -1,129 +1,239 @@
-use std::str::FromStr;
+use std::{ascii, num::NonZeroUsize, str::FromStr};
 
 use anyhow::Context;
-use itertools::{process_results, Itertools};
+use itertools::Itertools;
+use joinery::JoinableIterator;
 use lazy_format::lazy_format;
 
-use crate::library::{Counter, IterExt};
+use crate::{
+    input::{FromPuzzleInput, PuzzleInput},
+    library::Counter,
+    Options,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Item {
     id: u8,
 }
 
-impl Item {
-    pub fn new(id: u8) -> Option<Self> {
-        id.is_ascii_alphabetic().then_some(Item { id })
-    }
+/// The priority of each kind of item. Bytes that aren't in the table aren't
+/// valid items.
+#[derive(Debug, Clone)]
+pub struct PriorityTable {
+    priorities: [Option<i64>; 256],
+}
 
-    pub fn value(self) -> i64 {
-        if self.id.is_ascii_lowercase() {
-            (self.id - b'a' + 1) as i64
-        } else if self.id.is_ascii_uppercase() {
-            (self.id - b'A' + 27) as i64
-        } else {
-            unreachable!()
+impl PriorityTable {
+    /// Create a table from a list of items in order of increasing priority;
+    /// the first item has priority 1.
+    pub fn from_order(order: &[u8]) -> anyhow::Result<Self> {
+        let mut priorities = [None; 256];
+
+        for (priority, &id) in (1..).zip(order) {
+            let slot = &mut priorities[id as usize];
+            anyhow::ensure!(
+                slot.is_none(),
+                "item '{}' appears more than once",
+                ascii::escape_default(id)
+            );
+            *slot = Some(priority);
         }
+
+        Ok(Self { priorities })
+    }
+
+    /// The standard scheme: a through z have priorities 1 through 26, and A
+    /// through Z have priorities 27 through 52.
+    pub fn standard() -> Self {
+        let order: Vec<u8> = (b'a'..=b'z').chain(b'A'..=b'Z').collect();
+        Self::from_order(&order).expect("standard priorities have no duplicates")
+    }
+
+    fn item(&self, id: u8) -> Option<Item> {
+        self.priorities[id as usize].map(|_| Item { id })
+    }
+
+    fn priority(&self, item: Item) -> i64 {
+        self.priorities[item.id as usize].expect("items are always in the priority table")
     }
 }
 
-#[derive(Debug, Clone, Default)]
-struct Compartment {
-    items: Counter<Item>,
+impl Default for PriorityTable {
+    fn default() -> Self {
+        Self::standard()
+    }
 }
 
-impl FromStr for Compartment {
+impl FromStr for PriorityTable {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> anyhow::Result<Self> {
-        s.as_bytes()
+        Self::from_order(s.as_bytes())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Compartment {
+    items: Counter<Item>,
+}
+
+impl Compartment {
+    /// Parse a compartment, which starts at `offset` in its line. On failure,
+    /// returns every invalid byte, along with its (1-based) column.
+    fn parse(bytes: &[u8], offset: usize, table: &PriorityTable) -> Result<Self, Vec<(usize, u8)>> {
+        let (items, invalid): (Vec<Item>, Vec<(usize, u8)>) = bytes
             .iter()
-            .copied()
             .enumerate()
-            .map(|(idx, id)| {
-                Item::new(id).with_context(|| {
-                    lazy_format!("item '{}' at index {idx} is not a valid Item", id as char)
-                })
-            })
-            .try_collect()
-            .map(|items| Compartment { items })
+            .map(|(idx, &id)| table.item(id).ok_or((offset + idx + 1, id)))
+            .partition_result();
+
+        if !invalid.is_empty() {
+            return Err(invalid);
+        }
+
+        let mut counter = Counter::with_capacity(items.len());
+        items.into_iter().for_each(|item| counter.add(item, 1));
+
+        Ok(Compartment { items: counter })
     }
 }
 
 struct Sack {
-    first: Compartment,
-    second: Compartment,
+    compartments: Vec<Compartment>,
 }
 
 impl Sack {
+    /// Parse a sack, splitting it into `count` equally sized compartments
+    fn parse(s: &str, count: NonZeroUsize, table: &PriorityTable) -> anyhow::Result<Self> {
+        let count = count.get();
+
+        anyhow::ensure!(
+            s.len().is_multiple_of(count),
+            "sack has {} items, which can't be split into {count} compartments",
+            s.len()
+        );
+
+        let size = s.len() / count;
+
+        let (compartments, invalid): (Vec<Compartment>, Vec<Vec<(usize, u8)>>) = s
+            .as_bytes()
+            .chunks(size.max(1))
+            .enumerate()
+            .map(|(idx, bytes)| Compartment::parse(bytes, idx * size, table))
+            .partition_result();
+
+        anyhow::ensure!(
+            invalid.is_empty(),
+            "invalid items: {}",
+            invalid
+                .iter()
+                .flatten()
+                .map(|&(column, id)| lazy_format!(
+                    "'{}' at column {column}",
+                    ascii::escape_default(id)
+                ))
+                .join_with(", ")
+        );
+
+        Ok(Sack { compartments })
+    }
+
+    /// Items that are in every compartment of this sack
     pub fn shared(&self) -> impl Iterator<Item = Item> + '_ {
-        let (iter, filter) = match self.first.items.len() >= self.second.items.len() {
-            true => (&self.first, &self.second),
-            false => (&self.second, &self.first),
-        };
+        let smallest = self
+            .compartments
+            .iter()
+            .min_by_key(|compartment| compartment.items.len());
 
-        iter.items
-            .items()
+        smallest
+            .into_iter()
+            .flat_map(|smallest| smallest.items.items())
             .copied()
-            .filter(|item| filter.items.contains(item))
+            .filter(|item| {
+                self.compartments
+                    .iter()
+                    .all(|compartment| compartment.items.contains(item))
+            })
     }
 
     pub fn contains(&self, item: Item) -> bool {
-        self.first.items.contains(&item) || self.second.items.contains(&item)
+        self.compartments
+            .iter()
+            .any(|compartment| compartment.items.contains(&item))
     }
 
     pub fn items(&self) -> impl Iterator<Item = Item> + '_ {
-        self.first
-            .items
-            .items()
-            .chain(self.second.items.items())
+        self.compartments
+            .iter()
+            .flat_map(|compartment| compartment.items.items())
             .copied()
     }
 }
 
-impl FromStr for Sack {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> anyhow::Result<Self> {
-        let mid = s.len() / 2;
-        let (s1, s2) = s.split_at(mid);
+pub struct Input<'a> {
+    text: &'a str,
+    group_size: NonZeroUsize,
+    compartments: NonZeroUsize,
+    priorities: PriorityTable,
+}
 
+impl<'a> FromPuzzleInput<'a> for Input<'a> {
+    fn from_puzzle_input(input: &'a mut PuzzleInput, options: &'a Options) -> anyhow::Result<Self> {
         Ok(Self {
-            first: s1.parse().context("failed to parse first compartment")?,
-            second: s2.parse().context("failed to parse second compartment")?,
+            text: input.text()?,
+            group_size: options.group_size,
+            compartments: options.compartments,
+            priorities: options.priorities.clone().unwrap_or_default(),
         })
     }
 }
 
-pub fn part1(input: &str) -> anyhow::Result<i64> {
-    let sacks = input.lines().enumerate().map(|(index, line)| {
-        line.parse()
-            .context(lazy_format!("failed to parse sack on line {}", index + 1))
-    });
-
-    process_results(sacks, |sacks| {
-        sacks
-            .map(|sack: Sack| sack.shared().map(|item| item.value()).sum::<i64>())
-            .sum()
-    })
-}
-pub fn part2(input: &str) -> anyhow::Result<i64> {
-    let sacks = input.lines().enumerate().map(|(index, line)| {
-        line.parse()
-            .context(lazy_format!("failed to parse sack on line {}", index + 1))
-    });
-
-    process_results(sacks, |sacks| {
-        sacks
-            .streaming_chunks()
-            .map(|[a, b, c]: [Sack; 3]| {
-                let items = a.items();
-                items
-                    .filter(|&item| b.contains(item))
-                    .filter(|&item| c.contains(item))
-                    .map(|common_item| common_item.value())
-                    .sum::<i64>()
+impl Input<'_> {
+    fn sacks(&self) -> anyhow::Result<Vec<Sack>> {
+        self.text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| {
+                Sack::parse(line, self.compartments, &self.priorities)
+                    .context(lazy_format!("failed to parse sack on line {}", index + 1))
             })
-            .sum()
-    })
+            .collect()
+    }
+}
+
+pub fn part1(input: Input) -> anyhow::Result<i64> {
+    Ok(input
+        .sacks()?
+        .iter()
+        .map(|sack| {
+            sack.shared()
+                .map(|item| input.priorities.priority(item))
+                .sum::<i64>()
+        })
+        .sum())
+}
+
+pub fn part2(input: Input) -> anyhow::Result<i64> {
+    let sacks = input.sacks()?;
+    let group_size = input.group_size.get();
+
+    anyhow::ensure!(
+        sacks.len().is_multiple_of(group_size),
+        "there are {} sacks, which can't be split into groups of {group_size}",
+        sacks.len()
+    );
+
+    Ok(sacks
+        .chunks(group_size)
+        .map(|group| {
+            let (first, rest) = group.split_first().expect("groups are never empty");
+
+            first
+                .items()
+                .unique()
+                .filter(|&item| rest.iter().all(|sack| sack.contains(item)))
+                .map(|common_item| input.priorities.priority(common_item))
+                .sum::<i64>()
+        })
+        .sum())
 }
@@ -1,65 +1,44 @@
 use anyhow::Context;
-use nom::{
-    character::complete::{char, digit1, line_ending},
-    combinator::eof,
-    IResult, Parser,
-};
+use nom::{character::complete::char, IResult, Parser};
 use nom_supreme::{
     error::ErrorTree,
     final_parser::{final_parser, Location},
-    multi::parse_separated_terminated,
     ParserExt,
 };
 
-use crate::parser;
-
-/// Range of locations with inclusive min and max
-#[derive(Debug, Clone, Copy)]
-struct Range {
-    min: i64,
-    max: i64,
-}
-
-impl Range {
-    pub fn contains(&self, other: &Self) -> bool {
-        self.min <= other.min && self.max >= other.max
-    }
-
-    pub fn overlaps_into(&self, other: &Self) -> bool {
-        other.min <= self.min && self.min <= other.max
-            || other.min <= self.max && self.max <= other.max
-    }
-}
-
-fn parse_number(input: &str) -> IResult<&str, i64, ErrorTree<&str>> {
-    digit1.parse_from_str_cut().parse(input)
-}
+use crate::{
+    input::{FromPuzzleInput, PuzzleInput},
+    library::{
+        interval::{depths, Interval, IntervalSet},
+        parse::{lines, unsigned},
+    },
+    parser, Options,
+};
 
-fn parse_range(input: &str) -> IResult<&str, Range, ErrorTree<&str>> {
+fn parse_range(input: &str) -> IResult<&str, Interval<i64>, ErrorTree<&str>> {
     parser! {
-        parse_number.context("lower bound") => min,
+        "lower bound": unsigned() => min,
         char('-'),
-        parse_number.context("upper bound") => max;
-        Range { min, max }
+        "upper bound": unsigned() => max;
+        try Interval::new(min, max).context("lower bound is greater than upper bound")
     }
-    .verify(|range| range.min <= range.max)
     .parse(input)
 }
 
 #[derive(Debug, Clone, Copy)]
 struct RangePair {
-    first: Range,
-    second: Range,
+    first: Interval<i64>,
+    second: Interval<i64>,
 }
 
 impl RangePair {
     /// Returns true if one range is fully contained within the other
     fn fully_contained(&self) -> bool {
-        self.first.contains(&self.second) || self.second.contains(&self.first)
+        self.first.contains_interval(&self.second) || self.second.contains_interval(&self.first)
     }
 
     fn overlaps(&self) -> bool {
-        self.first.overlaps_into(&self.second) || self.second.overlaps_into(&self.first)
+        self.first.overlaps(&self.second)
     }
 }
 
@@ -73,33 +52,114 @@ fn parse_pair(input: &str) -> IResult<&str, RangePair, ErrorTree<&str>> {
     .parse(input)
 }
 
-fn count_pair_list_matching<'a>(
-    filter: impl Fn(&RangePair) -> bool,
-) -> impl Parser<&'a str, usize, ErrorTree<&'a str>> {
-    parse_separated_terminated(
-        parse_pair.context("range pair"),
-        line_ending,
-        eof.opt_preceded_by(line_ending),
-        || 0,
-        move |count, range| match filter(&range) {
-            true => count + 1,
-            false => count,
-        },
-    )
+fn parse_pair_list(input: &str) -> IResult<&str, Vec<RangePair>, ErrorTree<&str>> {
+    lines(parse_pair.context("range pair")).parse(input)
+}
+
+fn final_parse_pair_list(input: &str) -> Result<Vec<RangePair>, ErrorTree<Location>> {
+    final_parser(parse_pair_list)(input)
+}
+
+/// Facts about the whole list of assignments, rather than individual pairs
+#[derive(Debug, Clone)]
+struct Analysis {
+    /// The number of sections assigned to at least one elf
+    covered: i64,
+
+    /// The number of sections within the overall span that aren't assigned
+    /// to anybody
+    uncovered: i64,
+
+    /// The greatest number of elves assigned to any one section
+    max_depth: usize,
+
+    /// Elves (numbered from 1 in input order) whose sections are all assigned
+    /// to someone else too, along with their assignment
+    redundant: Vec<(usize, Interval<i64>)>,
 }
 
-fn final_count_pair_list_matching(
-    input: &str,
-    filter: impl Fn(&RangePair) -> bool,
-) -> Result<usize, ErrorTree<Location>> {
-    final_parser(count_pair_list_matching(filter))(input)
+impl Analysis {
+    fn new(pairs: &[RangePair]) -> Self {
+        let elves: Vec<Interval<i64>> = pairs
+            .iter()
+            .flat_map(|pair| [pair.first, pair.second])
+            .collect();
+
+        let coverage: IntervalSet<i64> = elves.iter().copied().collect();
+        let depths = depths(elves.iter().copied());
+
+        // An elf is redundant if every one of its sections is covered by at
+        // least one other elf
+        let redundant = elves
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, elf)| {
+                let start = depths.partition_point(|(segment, _)| segment.max() < elf.min());
+
+                depths[start..]
+                    .iter()
+                    .take_while(|(segment, _)| segment.min() <= elf.max())
+                    .all(|&(_, depth)| depth >= 2)
+            })
+            .map(|(idx, elf)| (idx + 1, elf))
+            .collect();
+
+        Self {
+            covered: coverage.len(),
+            uncovered: coverage.gaps().map(|gap| gap.len()).sum(),
+            max_depth: depths.iter().map(|&(_, depth)| depth).max().unwrap_or(0),
+            redundant,
+        }
+    }
+
+    fn print(&self) {
+        eprintln!("sections covered: {}", self.covered);
+        eprintln!("sections uncovered: {}", self.uncovered);
+        eprintln!("max overlap depth: {}", self.max_depth);
+        eprintln!("redundant elves: {}", self.redundant.len());
+
+        for (elf, sections) in &self.redundant {
+            eprintln!(
+                "  elf {elf} (pair {pair}): {min}-{max}",
+                pair = elf.div_ceil(2),
+                min = sections.min(),
+                max = sections.max(),
+            );
+        }
+    }
+}
+
+pub struct Input<'a> {
+    text: &'a str,
+    analyze: bool,
+}
+
+impl<'a> FromPuzzleInput<'a> for Input<'a> {
+    fn from_puzzle_input(input: &'a mut PuzzleInput, options: &'a Options) -> anyhow::Result<Self> {
+        Ok(Self {
+            text: input.text()?,
+            analyze: options.analyze,
+        })
+    }
+}
+
+/// Parse the pair list, print the analysis if requested, and count the pairs
+/// that match `filter`
+fn solve(input: Input, filter: impl Fn(&RangePair) -> bool) -> anyhow::Result<usize> {
+    let pairs = final_parse_pair_list(input.text).context("failed to parse input")?;
+
+    if input.analyze {
+        Analysis::new(&pairs).print();
+    }
+
+    Ok(pairs.iter().filter(|pair| filter(pair)).count())
 }
 
-pub fn part1(input: &str) -> anyhow::Result<usize> {
-    final_count_pair_list_matching(input, |range| range.fully_contained())
-        .context("failed to parse input")
+pub fn part1(input: Input) -> anyhow::Result<usize> {
+    solve(input, |range| range.fully_contained())
 }
 
-pub fn part2(input: &str) -> anyhow::Result<usize> {
-    final_count_pair_list_matching(input, |range| range.overlaps()).context("failed to parse input")
+pub fn part2(input: Input) -> anyhow::Result<usize> {
+    solve(input, |range| range.overlaps())
 }
@@ -1,6 +1,11 @@
-use std::{collections::BTreeMap, fmt::Display};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    iter,
+};
 
 use anyhow::Context;
+use joinery::JoinableIterator;
 use lazy_format::lazy_format;
 use nom::{
     branch::alt,
@@ -17,7 +22,11 @@ use nom_supreme::{
     ParserExt,
 };
 
-use crate::parser;
+use crate::{
+    input::{FromPuzzleInput, PuzzleInput},
+    library::parse::unsigned,
+    parser, Options,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Crate {
@@ -90,6 +99,7 @@ fn parse_stack_label_row<'a>(
     })
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Stacks<'a> {
     stacks: BTreeMap<StackLabel<'a>, Vec<Crate>>,
 }
@@ -143,63 +153,309 @@ struct Command<'a> {
     destination: StackLabel<'a>,
 }
 
+impl Display for Command<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "move {} from {} to {}",
+            self.count, self.origin.label, self.destination.label
+        )
+    }
+}
+
+/// Renders the stacks in the same format as the puzzle input: one row of
+/// crates per line, from the top down, with every row padded to the full
+/// width, followed by the row of stack labels.
+impl Display for Stacks<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let height = self.stacks.values().map(Vec::len).max().unwrap_or(0);
+
+        for level in (0..height).rev() {
+            let row = self.stacks.values().map(|stack| {
+                lazy_format!(match (stack.get(level)) {
+                    Some(Crate { id }) => "[{id}]",
+                    None => "   ",
+                })
+            });
+
+            writeln!(f, "{}", row.join_with(' '))?;
+        }
+
+        let labels = self
+            .stacks
+            .keys()
+            .map(|label| lazy_format!(" {} ", label.label));
+
+        writeln!(f, "{}", labels.join_with(' '))
+    }
+}
+
+/// A movement of the top `count` crates from one stack to another, which
+/// keeps them in the same order. This is the primitive operation that every
+/// crane model is built from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Transfer<'a> {
+    count: usize,
+    origin: StackLabel<'a>,
+    destination: StackLabel<'a>,
+}
+
+impl Transfer<'_> {
+    /// The transfer that exactly undoes this one
+    fn inverse(self) -> Self {
+        Self {
+            count: self.count,
+            origin: self.destination,
+            destination: self.origin,
+        }
+    }
+}
+
+impl Display for Transfer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} from {} to {}",
+            self.count, self.origin.label, self.destination.label
+        )
+    }
+}
+
 impl<'a> Stacks<'a> {
-    pub fn apply_move(
-        &mut self,
-        origin: StackLabel<'a>,
-        destination: StackLabel<'a>,
-    ) -> anyhow::Result<()> {
+    /// Apply a transfer. Nothing is moved if this fails.
+    pub fn transfer(&mut self, transfer: Transfer<'a>) -> anyhow::Result<()> {
+        let Transfer {
+            count,
+            origin,
+            destination,
+        } = transfer;
+
+        anyhow::ensure!(
+            self.stacks.contains_key(&destination),
+            "destination stack doesn't exist"
+        );
+
         let origin = self
             .stacks
             .get_mut(&origin)
             .context("origin stack doesn't exist")?;
-        let moved_crate = origin.pop().context("origin stack is empty")?;
-        let destination = self
-            .stacks
+
+        let split = origin
+            .len()
+            .checked_sub(count)
+            .context("origin stack doesn't have enough crates")?;
+
+        let moved: Vec<Crate> = origin.drain(split..).collect();
+
+        self.stacks
             .get_mut(&destination)
-            .context("destination stack doesn't exist")?;
-        destination.push(moved_crate);
+            .expect("already checked that the destination exists")
+            .extend(moved);
 
         Ok(())
     }
 
-    pub fn apply_command(&mut self, command: &Command<'a>) -> anyhow::Result<()> {
-        (0..command.count).try_for_each(|n| {
-            self.apply_move(command.origin, command.destination)
-                .context(lazy_format!("failed during move #{}", n + 1))
-        })
+    /// The position of a stack, from left to right
+    fn position(&self, label: StackLabel<'a>) -> Option<usize> {
+        self.stacks.keys().position(|&candidate| candidate == label)
     }
+}
 
-    pub fn apply_command_v2(&mut self, command: &Command<'a>) -> anyhow::Result<()> {
-        let mut stack = Vec::with_capacity(command.count);
+/// A model of crane, which carries out each command as a series of transfers
+trait CraneModel {
+    /// Plan the transfers that carry out `command`. The plan isn't checked
+    /// against the contents of the stacks; that happens when it's applied.
+    fn plan<'a>(
+        &self,
+        stacks: &Stacks<'a>,
+        command: &Command<'a>,
+    ) -> anyhow::Result<Vec<Transfer<'a>>>;
+}
 
-        let origin = self
-            .stacks
-            .get_mut(&command.origin)
-            .context("origin stack doesn't exist")?;
+/// Moves one crate at a time
+#[derive(Debug, Clone, Copy)]
+struct CrateMover9000;
+
+impl CraneModel for CrateMover9000 {
+    fn plan<'a>(
+        &self,
+        _stacks: &Stacks<'a>,
+        command: &Command<'a>,
+    ) -> anyhow::Result<Vec<Transfer<'a>>> {
+        let transfer = Transfer {
+            count: 1,
+            origin: command.origin,
+            destination: command.destination,
+        };
+
+        Ok(vec![transfer; command.count])
+    }
+}
+
+/// Moves all of the crates in a command at once
+#[derive(Debug, Clone, Copy)]
+struct CrateMover9001;
+
+impl CraneModel for CrateMover9001 {
+    fn plan<'a>(
+        &self,
+        _stacks: &Stacks<'a>,
+        command: &Command<'a>,
+    ) -> anyhow::Result<Vec<Transfer<'a>>> {
+        Ok(vec![Transfer {
+            count: command.count,
+            origin: command.origin,
+            destination: command.destination,
+        }])
+    }
+}
 
-        (0..command.count).try_for_each(|_n| {
-            origin
-                .pop()
-                .context("origin stack doesn't have enough crates")
-                .map(|moved_crate| stack.push(moved_crate))
-        })?;
+/// Like the 9001, but can only lift so many crates at once, so larger
+/// commands are split into several lifts.
+#[derive(Debug, Clone, Copy)]
+struct LimitedLift {
+    max_lift: usize,
+}
 
-        let destination = self
-            .stacks
-            .get_mut(&command.destination)
+impl CraneModel for LimitedLift {
+    fn plan<'a>(
+        &self,
+        _stacks: &Stacks<'a>,
+        command: &Command<'a>,
+    ) -> anyhow::Result<Vec<Transfer<'a>>> {
+        let full_lifts = command.count / self.max_lift;
+        let last_lift = command.count % self.max_lift;
+
+        Ok(iter::repeat_n(self.max_lift, full_lifts)
+            .chain((last_lift > 0).then_some(last_lift))
+            .map(|count| Transfer {
+                count,
+                origin: command.origin,
+                destination: command.destination,
+            })
+            .collect())
+    }
+}
+
+/// Like the 9000, but can only move crates between adjacent stacks
+#[derive(Debug, Clone, Copy)]
+struct AdjacentOnly;
+
+impl CraneModel for AdjacentOnly {
+    fn plan<'a>(
+        &self,
+        stacks: &Stacks<'a>,
+        command: &Command<'a>,
+    ) -> anyhow::Result<Vec<Transfer<'a>>> {
+        let origin = stacks
+            .position(command.origin)
+            .context("origin stack doesn't exist")?;
+        let destination = stacks
+            .position(command.destination)
             .context("destination stack doesn't exist")?;
 
-        destination.extend(stack.iter().rev().copied());
+        anyhow::ensure!(
+            origin.abs_diff(destination) == 1,
+            "stacks {} and {} aren't adjacent",
+            command.origin.label,
+            command.destination.label,
+        );
 
+        CrateMover9000.plan(stacks, command)
+    }
+}
+
+/// Stacks, along with a log of the transfers that each applied command was
+/// carried out with, so that commands can be undone.
+struct Simulation<'a> {
+    stacks: Stacks<'a>,
+    log: Vec<Vec<Transfer<'a>>>,
+}
+
+impl<'a> Simulation<'a> {
+    fn new(stacks: Stacks<'a>) -> Self {
+        Self {
+            stacks,
+            log: Vec::new(),
+        }
+    }
+
+    /// Apply a command with the given crane. If it fails partway through, the
+    /// transfers that did succeed are undone, so the stacks are left as they
+    /// were before the command, and the error describes exactly how far the
+    /// command got.
+    fn apply(&mut self, crane: &dyn CraneModel, command: &Command<'a>) -> anyhow::Result<()> {
+        let plan = crane.plan(&self.stacks, command)?;
+        let mut applied = Vec::with_capacity(plan.len());
+
+        for (idx, &transfer) in plan.iter().enumerate() {
+            if let Err(err) = self.stacks.transfer(transfer) {
+                self.log.push(applied);
+                let completed = self.undo().expect("just pushed a log entry");
+
+                return Err(err)
+                    .context(format!(
+                        "failed during transfer #{step} of {total} ({transfer})",
+                        step = idx + 1,
+                        total = plan.len(),
+                    ))
+                    .context(format!(
+                        "transfers completed before the failure: {}",
+                        match completed.is_empty() {
+                            true => "none".to_owned(),
+                            false => completed.iter().join_with(", ").to_string(),
+                        }
+                    ))
+                    .context(format!(
+                        "stacks before the failing command:\n{}",
+                        self.stacks.to_string().trim_end()
+                    ));
+            }
+
+            applied.push(transfer);
+        }
+
+        self.log.push(applied);
         Ok(())
     }
+
+    /// Undo the most recently applied command, returning the transfers it
+    /// was carried out with, or `None` if there are no commands to undo.
+    fn undo(&mut self) -> Option<Vec<Transfer<'a>>> {
+        let transfers = self.log.pop()?;
+
+        transfers.iter().rev().for_each(|transfer| {
+            self.stacks
+                .transfer(transfer.inverse())
+                .expect("undoing a transfer should always succeed")
+        });
+
+        Some(transfers)
+    }
+}
+
+/// The crane models that can be selected from the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Crane {
+    /// The CrateMover 9000, which moves one crate at a time
+    #[value(name = "9000")]
+    CrateMover9000,
+
+    /// The CrateMover 9001, which moves many crates at once
+    #[value(name = "9001")]
+    CrateMover9001,
+
+    /// A 9001 that can only lift --max-lift crates at once
+    Limited,
+
+    /// A 9000 that can only move crates between adjacent stacks
+    Adjacent,
 }
 
 fn parse_command(input: &str) -> IResult<&str, Command<'_>, ErrorTree<&str>> {
     parser! {
         tag("move "),
-        digit1.parse_from_str().context("count") => count,
+        "count": unsigned() => count,
         tag(" from "),
         parse_stack_label.context("origin") => origin,
         tag(" to "),
@@ -228,30 +484,140 @@ fn final_parse_problem(input: &str) -> Result<(Stacks<'_>, Vec<Command<'_>>), Er
     final_parser(parse_problem)(input)
 }
 
-fn solve<'a>(
-    input: &'a str,
-    apply_command: impl Fn(&mut Stacks<'a>, &Command<'a>) -> anyhow::Result<()>,
-) -> anyhow::Result<impl Display + '_> {
-    let (mut stacks, commands) = final_parse_problem(input).context("failed to parse input")?;
+pub struct Input<'a> {
+    text: &'a str,
+    crane: Option<Crane>,
+    max_lift: usize,
+    trace: bool,
+}
+
+impl<'a> FromPuzzleInput<'a> for Input<'a> {
+    fn from_puzzle_input(input: &'a mut PuzzleInput, options: &'a Options) -> anyhow::Result<Self> {
+        Ok(Self {
+            text: input.text()?,
+            crane: options.crane,
+            max_lift: options.max_lift.get(),
+            trace: options.trace,
+        })
+    }
+}
+
+impl Input<'_> {
+    /// The crane model to use, if one was chosen on the command line, or else
+    /// `default`.
+    fn crane(&self, default: Crane) -> Box<dyn CraneModel> {
+        match self.crane.unwrap_or(default) {
+            Crane::CrateMover9000 => Box::new(CrateMover9000),
+            Crane::CrateMover9001 => Box::new(CrateMover9001),
+            Crane::Limited => Box::new(LimitedLift {
+                max_lift: self.max_lift,
+            }),
+            Crane::Adjacent => Box::new(AdjacentOnly),
+        }
+    }
+}
+
+fn solve<'a>(input: Input<'a>, default_crane: Crane) -> anyhow::Result<impl Display + 'a> {
+    let crane = input.crane(default_crane);
+    let (stacks, commands) = final_parse_problem(input.text).context("failed to parse input")?;
+    let mut simulation = Simulation::new(stacks);
+
+    // In trace mode, print the stacks after each command
+    let trace = |stacks: &Stacks| {
+        if input.trace {
+            eprintln!("{stacks}");
+        }
+    };
+
+    trace(&simulation.stacks);
 
     commands
         .iter()
         .enumerate()
         .try_for_each(|(idx, command)| {
-            apply_command(&mut stacks, command)
-                .context(lazy_format!("failed to apply command #{}", idx + 1))
+            if input.trace {
+                eprintln!("{command}");
+            }
+
+            simulation
+                .apply(crane.as_ref(), command)
+                .context(lazy_format!("failed to apply command #{}", idx + 1))?;
+
+            trace(&simulation.stacks);
+            anyhow::Ok(())
         })
         .context("error while applying commands")?;
 
     Ok(
-        lazy_format!("{label}" for Crate{id: label} in stacks.stacks.values().filter_map(|stack| stack.last())),
+        lazy_format!("{label}" for Crate{id: label} in simulation.stacks.stacks.values().filter_map(|stack| stack.last())),
     )
 }
 
-pub fn part1(input: &str) -> anyhow::Result<impl Display + '_> {
-    solve(input, |stacks, command| stacks.apply_command(command))
+pub fn part1(input: Input<'_>) -> anyhow::Result<impl Display + '_> {
+    solve(input, Crane::CrateMover9000)
 }
 
-pub fn part2(input: &str) -> anyhow::Result<impl Display + '_> {
-    solve(input, |stacks, command| stacks.apply_command_v2(command))
+pub fn part2(input: Input<'_>) -> anyhow::Result<impl Display + '_> {
+    solve(input, Crane::CrateMover9001)
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+
+    const LABELS: [&str; 9] = ["1", "2", "3", "4", "5", "6", "7", "8", "9"];
+
+    /// Build stacks with the given heights, filling them with crates A
+    /// through Z in order
+    fn build_stacks(heights: &[usize]) -> Stacks<'static> {
+        let mut ids = ('A'..='Z').cycle();
+
+        Stacks {
+            stacks: LABELS
+                .iter()
+                .zip(heights)
+                .map(|(&label, &height)| {
+                    let stack = ids.by_ref().take(height).map(Crate::new).collect();
+                    (StackLabel { label }, stack)
+                })
+                .collect(),
+        }
+    }
+
+    fn assert_round_trip(stacks: &Stacks) {
+        let rendered = stacks.to_string();
+        let parsed: Result<Stacks, ErrorTree<Location>> = final_parser(parse_stacks)(&rendered);
+
+        match parsed {
+            Ok(parsed) => assert_eq!(parsed, *stacks, "rendered as:\n{rendered}"),
+            Err(err) => panic!("failed to parse rendered stacks:\n{rendered}\n{err}"),
+        }
+    }
+
+    #[test]
+    fn round_trip_every_small_arrangement() {
+        for count in 1..=4 {
+            for heights in iter::repeat_n(0..=3, count).multi_cartesian_product() {
+                assert_round_trip(&build_stacks(&heights));
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_many_stacks() {
+        assert_round_trip(&build_stacks(&[0, 8, 1, 0, 3, 5, 0, 2, 0]));
+        assert_round_trip(&build_stacks(&[0; 9]));
+    }
+
+    #[test]
+    fn round_trip_puzzle_example() {
+        let text = "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n";
+        let stacks: Result<Stacks, ErrorTree<Location>> = final_parser(parse_stacks)(text);
+        let stacks = stacks.expect("example should parse");
+
+        assert_eq!(stacks.to_string(), text);
+        assert_round_trip(&stacks);
+    }
 }
@@ -1,28 +1,209 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufReader, Read, Write},
+    num::NonZeroUsize,
+};
+
 use anyhow::Context;
 
-pub fn unique<T: Eq>(mut input: &[T]) -> bool {
-    loop {
-        input = match input {
-            [] | [_] => break true,
-            [a, tail @ ..] => match tail.iter().all(|b| a != b) {
-                false => break false,
-                true => tail,
-            },
-        };
+use crate::{
+    input::{FromPuzzleInput, InputStream, PuzzleInput},
+    Options,
+};
+
+/// How many times each byte appears in a sliding window, and how many
+/// distinct bytes appear more than once. The window is all unique when that
+/// number is 0, so checking it is O(1) no matter how wide the window is.
+struct WindowCounts {
+    counts: [usize; 256],
+    repeated: usize,
+}
+
+impl WindowCounts {
+    fn new() -> Self {
+        Self {
+            counts: [0; 256],
+            repeated: 0,
+        }
+    }
+
+    fn add(&mut self, byte: u8) {
+        let count = &mut self.counts[byte as usize];
+        *count += 1;
+
+        if *count == 2 {
+            self.repeated += 1;
+        }
+    }
+
+    fn remove(&mut self, byte: u8) {
+        let count = &mut self.counts[byte as usize];
+        *count -= 1;
+
+        if *count == 1 {
+            self.repeated -= 1;
+        }
+    }
+
+    fn all_unique(&self) -> bool {
+        self.repeated == 0
+    }
+}
+
+/// Detects markers of `width` distinct bytes in a stream of bytes, which are
+/// fed to it one at a time. Only the current window is kept in memory.
+struct MarkerDetector {
+    width: usize,
+    window: VecDeque<u8>,
+    counts: WindowCounts,
+
+    /// The number of bytes fed to the detector so far
+    offset: usize,
+}
+
+impl MarkerDetector {
+    fn new(width: NonZeroUsize) -> Self {
+        Self {
+            width: width.get(),
+            window: VecDeque::with_capacity(width.get()),
+            counts: WindowCounts::new(),
+            offset: 0,
+        }
+    }
+
+    /// Feed the next byte to the detector. If it completes a marker, returns
+    /// the absolute offset just past the end of that marker; that is, the
+    /// total number of bytes that had to be read to complete it.
+    fn push(&mut self, byte: u8) -> Option<usize> {
+        if self.window.len() == self.width {
+            let outgoing = self.window.pop_front().expect("window is full");
+            self.counts.remove(outgoing);
+        }
+
+        self.window.push_back(byte);
+        self.counts.add(byte);
+        self.offset += 1;
+
+        (self.window.len() == self.width && self.counts.all_unique()).then_some(self.offset)
+    }
+}
+
+/// Find every marker of `width` distinct bytes in a stream, in O(n) time,
+/// reading it incrementally. The signal ends at the first line ending.
+fn markers(reader: impl Read, width: NonZeroUsize) -> impl Iterator<Item = io::Result<usize>> {
+    let mut detector = MarkerDetector::new(width);
+
+    BufReader::new(reader)
+        .bytes()
+        .take_while(|byte| !matches!(byte, Ok(b'\n' | b'\r')))
+        .filter_map(move |byte| match byte {
+            Ok(byte) => detector.push(byte).map(Ok),
+            Err(err) => Some(Err(err)),
+        })
+}
+
+pub struct Input {
+    stream: InputStream,
+    width: Option<NonZeroUsize>,
+    all_markers: bool,
+}
+
+impl FromPuzzleInput<'_> for Input {
+    fn from_puzzle_input(input: &mut PuzzleInput, options: &Options) -> anyhow::Result<Self> {
+        Ok(Self {
+            stream: input.stream(),
+            width: options.width,
+            all_markers: options.all_markers,
+        })
     }
 }
 
-fn start_of_marker_idx<T: Eq>(input: &[T], width: usize) -> Option<usize> {
-    input
-        .windows(width)
-        .position(|window| unique(window))
-        .map(|idx| idx + width)
+/// Find the first marker, or every marker (one per line) in all-markers mode.
+/// In all-markers mode, each marker but the last is written to stdout as soon
+/// as it's found, and the last one is returned as the solution, so that the
+/// markers never have to be collected.
+fn solve(input: Input, default_width: usize, name: &str) -> anyhow::Result<usize> {
+    let width = input
+        .width
+        .or(NonZeroUsize::new(default_width))
+        .expect("default widths aren't 0");
+
+    let mut markers = markers(input.stream, width);
+
+    let marker = if input.all_markers {
+        let mut stdout = io::stdout().lock();
+
+        markers.try_fold(None, |previous, marker| {
+            let marker = marker.context("failed to read signal")?;
+
+            if let Some(previous) = previous {
+                writeln!(stdout, "{previous}").context("failed to write marker")?;
+            }
+
+            anyhow::Ok(Some(marker))
+        })?
+    } else {
+        markers
+            .next()
+            .transpose()
+            .context("failed to read signal")?
+    };
+
+    marker.with_context(|| format!("No {name} marker"))
+}
+
+pub fn part1(input: Input) -> anyhow::Result<usize> {
+    solve(input, 4, "start-of-packet")
 }
 
-pub fn part1(input: &str) -> anyhow::Result<usize> {
-    start_of_marker_idx(input.as_bytes(), 4).context("No start-of-packet marker")
+pub fn part2(input: Input) -> anyhow::Result<usize> {
+    solve(input, 14, "start-of-message")
 }
 
-pub fn part2(input: &str) -> anyhow::Result<usize> {
-    start_of_marker_idx(input.as_bytes(), 14).context("No start-of-message marker")
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLES: [(&str, usize, usize); 4] = [
+        ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 19),
+        ("bvwbjplbgvbhsrlpgdmjqwftvncz", 5, 23),
+        ("nppdvjthqldpwncqzvftbrmjlhg", 6, 23),
+        ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 11, 26),
+    ];
+
+    fn all_markers(signal: &str, width: usize) -> Vec<usize> {
+        let width = NonZeroUsize::new(width).expect("width isn't 0");
+
+        markers(signal.as_bytes(), width)
+            .collect::<io::Result<_>>()
+            .expect("reading from a slice can't fail")
+    }
+
+    #[test]
+    fn first_markers() {
+        for (signal, packet, message) in EXAMPLES {
+            assert_eq!(all_markers(signal, 4).first(), Some(&packet), "{signal}");
+            assert_eq!(all_markers(signal, 14).first(), Some(&message), "{signal}");
+        }
+    }
+
+    #[test]
+    fn every_marker() {
+        let (signal, _, _) = EXAMPLES[0];
+
+        assert_eq!(all_markers(signal, 4), (7..=30).collect::<Vec<_>>());
+        assert_eq!(all_markers(signal, 14), [19, 25, 26, 27, 28, 29, 30]);
+    }
+
+    #[test]
+    fn line_ending_is_not_part_of_the_signal() {
+        for (signal, _, _) in EXAMPLES {
+            for ending in ["\n", "\r\n"] {
+                let terminated = format!("{signal}{ending}");
+
+                assert_eq!(all_markers(&terminated, 4), all_markers(signal, 4));
+                assert_eq!(all_markers(&terminated, 14), all_markers(signal, 14));
+            }
+        }
+    }
 }
@@ -1,51 +1,162 @@
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    cmp::Reverse,
+    collections::{btree_map, BTreeMap, BTreeSet, VecDeque},
+    fmt::{self, Write as _},
+    fs, mem,
+    path::{Path, PathBuf},
+};
 
-use anyhow::Context;
+use anyhow::{bail, ensure, Context};
 use nom::{
     branch::alt,
     bytes::complete::take_until1,
-    character::complete::{char, digit1, space0, space1},
+    character::complete::{char, space0, space1},
     combinator::{eof, success},
     error::{ErrorKind, FromExternalError, ParseError},
+    multi::many0_count,
     IResult, Parser,
 };
 use nom_supreme::{
     error::ErrorTree,
     final_parser::{final_parser, Location},
-    multi::parse_separated_terminated,
+    multi::collect_separated_terminated,
     tag::complete::tag,
     ParserExt,
 };
 
+use crate::{
+    input::{FromPuzzleInput, PuzzleInput},
+    library::parse::unsigned,
+    Options,
+};
+
 #[derive(Debug, Copy, Clone)]
 struct File {
     size: usize,
 }
 
+/// A directory in the emulated filesystem. Paths are given as a list of
+/// names, relative to this directory.
 #[derive(Debug, Clone, Default)]
 struct Directory<'a> {
-    entries: HashMap<&'a str, Node<'a>>,
-}
-
-impl Directory<'_> {
-    pub fn size(&self) -> usize {
-        self.entries.values().map(|node| node.size()).sum()
-    }
+    entries: BTreeMap<&'a str, Node<'a>>,
 }
 
 impl<'a> Directory<'a> {
-    pub fn add_file(&mut self, name: &'a str, size: usize) {
-        self.entries.insert(name, Node::File(File { size }));
+    /// Get the directory at `path`
+    pub fn directory(&self, path: &[&'a str]) -> anyhow::Result<&Self> {
+        path.iter()
+            .enumerate()
+            .try_fold(self, |dir, (idx, name)| match dir.entries.get(name) {
+                None => bail!("{} doesn't exist", display_path(&path[..=idx])),
+                Some(Node::File(_)) => bail!("{} is a file", display_path(&path[..=idx])),
+                Some(Node::Directory(dir)) => Ok(dir),
+            })
     }
 
-    pub fn add_directory(&mut self, name: &'a str) {
-        self.entries
-            .entry(name)
-            .and_modify(|node| match node {
-                Node::File(_) => *node = Node::Directory(Directory::default()),
-                Node::Directory(_) => {}
+    /// Get the directory at `path`, mutably
+    pub fn directory_mut(&mut self, path: &[&'a str]) -> anyhow::Result<&mut Self> {
+        path.iter()
+            .enumerate()
+            .try_fold(self, |dir, (idx, name)| match dir.entries.get_mut(name) {
+                None => bail!("{} doesn't exist", display_path(&path[..=idx])),
+                Some(Node::File(_)) => bail!("{} is a file", display_path(&path[..=idx])),
+                Some(Node::Directory(dir)) => Ok(dir),
             })
-            .or_insert_with(|| Node::Directory(Directory::default()));
+    }
+
+    /// Get the directory containing `path`, along with the name of the final
+    /// component of `path`. Fails if `path` is the root directory.
+    fn parent_mut(&mut self, path: &[&'a str]) -> anyhow::Result<(&mut Self, &'a str)> {
+        let (&name, parent) = path
+            .split_last()
+            .context("the root directory can't be modified")?;
+
+        Ok((self.directory_mut(parent)?, name))
+    }
+
+    /// Create a new, empty directory at `path`
+    pub fn make_directory(&mut self, path: &[&'a str]) -> anyhow::Result<()> {
+        let (parent, name) = self.parent_mut(path)?;
+
+        match parent.entries.entry(name) {
+            btree_map::Entry::Occupied(_) => bail!("{} already exists", display_path(path)),
+            btree_map::Entry::Vacant(slot) => {
+                slot.insert(Node::Directory(Directory::default()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create a file at `path`, or change its size if it already exists
+    pub fn write_file(&mut self, path: &[&'a str], size: usize) -> anyhow::Result<()> {
+        let (parent, name) = self.parent_mut(path)?;
+
+        match parent.entries.get_mut(name) {
+            Some(Node::Directory(_)) => bail!("{} is a directory", display_path(path)),
+            Some(Node::File(file)) => file.size = size,
+            None => {
+                parent.entries.insert(name, Node::File(File { size }));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove the file or directory (and everything in it) at `path`
+    pub fn remove(&mut self, path: &[&'a str]) -> anyhow::Result<Node<'a>> {
+        let (parent, name) = self.parent_mut(path)?;
+
+        parent
+            .entries
+            .remove(name)
+            .with_context(|| format!("{} doesn't exist", display_path(path)))
+    }
+
+    /// Record the output of an `ls` of this directory. Entries we haven't
+    /// seen before are added; entries we have seen must match what we already
+    /// know, and nothing we know about can be missing from the listing.
+    pub fn apply_listing(&mut self, listing: &[Entry<'a>]) -> anyhow::Result<()> {
+        let mut listed = BTreeSet::new();
+
+        for entry in listing {
+            ensure!(
+                listed.insert(entry.name),
+                "{:?} is listed more than once",
+                entry.name
+            );
+
+            match (self.entries.get(entry.name), entry.kind) {
+                (None, EntryKind::File(size)) => {
+                    self.entries.insert(entry.name, Node::File(File { size }));
+                }
+                (None, EntryKind::Directory) => {
+                    self.entries
+                        .insert(entry.name, Node::Directory(Directory::default()));
+                }
+                (Some(Node::File(file)), EntryKind::File(size)) => ensure!(
+                    file.size == size,
+                    "{:?} is listed with size {size}, but it was previously {}",
+                    entry.name,
+                    file.size
+                ),
+                (Some(Node::Directory(_)), EntryKind::Directory) => {}
+                (Some(Node::File(_)), EntryKind::Directory) => {
+                    bail!("{:?} is listed as a directory, but it's a file", entry.name)
+                }
+                (Some(Node::Directory(_)), EntryKind::File(_)) => {
+                    bail!("{:?} is listed as a file, but it's a directory", entry.name)
+                }
+            }
+        }
+
+        if let Some(missing) = self.entries.keys().find(|name| !listed.contains(*name)) {
+            bail!("{missing:?} exists, but is missing from the listing");
+        }
+
+        Ok(())
     }
 }
 
@@ -55,20 +166,77 @@ enum Node<'a> {
     Directory(Directory<'a>),
 }
 
-impl Node<'_> {
-    pub fn size(&self) -> usize {
-        match self {
-            Node::File(file) => file.size,
-            Node::Directory(dir) => dir.size(),
+/// Render a list of path components as an absolute path
+fn display_path(path: &[&str]) -> String {
+    format!("/{}", path.join("/"))
+}
+
+/// A path given as an argument to a command
+#[derive(Debug, Clone)]
+struct PathArg<'a> {
+    absolute: bool,
+    components: Vec<&'a str>,
+}
+
+impl<'a> PathArg<'a> {
+    fn new(path: &'a str) -> Self {
+        Self {
+            absolute: path.starts_with('/'),
+            components: path
+                .split('/')
+                .filter(|component| !component.is_empty())
+                .collect(),
         }
     }
+
+    /// Resolve this path, relative to `cwd`, into a list of names starting
+    /// from the root. `..` at the root stays at the root.
+    fn resolve(&self, cwd: &[&'a str]) -> Vec<&'a str> {
+        let base = match self.absolute {
+            true => Vec::new(),
+            false => cwd.to_vec(),
+        };
+
+        self.components.iter().fold(base, |mut path, &component| {
+            match component {
+                "." => {}
+                ".." => {
+                    path.pop();
+                }
+                name => path.push(name),
+            }
+            path
+        })
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 enum Destination<'a> {
-    Root,
-    Up,
-    Directory(&'a str),
+    Previous,
+    Path(PathArg<'a>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum EntryKind {
+    File(usize),
+    Directory,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry<'a> {
+    name: &'a str,
+    kind: EntryKind,
+}
+
+/// A command in the terminal transcript, along with its output
+#[derive(Debug, Clone)]
+enum ShellCommand<'a> {
+    Cd(Destination<'a>),
+    Ls(Vec<Entry<'a>>),
+    Pwd(&'a str),
+    Mkdir(PathArg<'a>),
+    Rm(PathArg<'a>),
+    Touch { size: usize, path: PathArg<'a> },
 }
 
 fn parse_command<'a, T>(
@@ -83,44 +251,35 @@ fn parse_command<'a, T>(
         .terminated(tag("\n"))
 }
 
-fn parse_cd(input: &str) -> IResult<&str, Destination<'_>, ErrorTree<&str>> {
-    parse_command(
-        "cd",
-        take_until1("\n")
-            .context("cd destination")
-            .preceded_by(space1),
-    )
-    .map(|argument: &str| match argument.trim() {
-        "/" => Destination::Root,
-        ".." => Destination::Up,
-        directory => Destination::Directory(directory),
-    })
-    .parse(input)
+/// Parse the rest of the line, without leading or trailing whitespace
+fn parse_argument(input: &str) -> IResult<&str, &str, ErrorTree<&str>> {
+    take_until1("\n")
+        .map(str::trim_end)
+        .preceded_by(space1)
+        .parse(input)
 }
 
-fn parse_ls_command(input: &str) -> IResult<&str, (), ErrorTree<&str>> {
-    parse_command("ls", success(())).parse(input)
+fn parse_path(input: &str) -> IResult<&str, PathArg<'_>, ErrorTree<&str>> {
+    parse_argument.map(PathArg::new).parse(input)
 }
 
-#[derive(Debug, Clone, Copy)]
-enum EntryKind {
-    File(usize),
-    Directory,
+fn parse_cd(input: &str) -> IResult<&str, Destination<'_>, ErrorTree<&str>> {
+    parse_command("cd", parse_argument.context("cd destination"))
+        .map(|argument| match argument {
+            "-" => Destination::Previous,
+            path => Destination::Path(PathArg::new(path)),
+        })
+        .parse(input)
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Entry<'a> {
-    name: &'a str,
-    kind: EntryKind,
+fn parse_ls_command(input: &str) -> IResult<&str, (), ErrorTree<&str>> {
+    parse_command("ls", success(())).parse(input)
 }
 
 fn parse_entry(input: &str) -> IResult<&str, Entry<'_>, ErrorTree<&str>> {
     alt((
         tag("dir").value(EntryKind::Directory),
-        digit1
-            .parse_from_str_cut()
-            .map(EntryKind::File)
-            .context("size"),
+        unsigned().map(EntryKind::File).context("size"),
     ))
     .terminated(space1)
     .and(take_until1("\n").context("name"))
@@ -128,91 +287,128 @@ fn parse_entry(input: &str) -> IResult<&str, Entry<'_>, ErrorTree<&str>> {
     .parse(input)
 }
 
-fn parse_ls_output(input: &str) -> IResult<&str, Directory<'_>, ErrorTree<&str>> {
-    parse_separated_terminated(
+fn parse_ls_output(input: &str) -> IResult<&str, Vec<Entry<'_>>, ErrorTree<&str>> {
+    collect_separated_terminated(
         parse_entry.terminated(tag("\n")).context("entry"),
         success(()),
         alt((eof, tag("$"))).peek(),
-        Directory::default,
-        |mut entries, entry| {
-            match entry.kind {
-                EntryKind::Directory => entries.add_directory(entry.name),
-                EntryKind::File(size) => entries.add_file(entry.name, size),
-            }
+    )
+    .or(alt((eof, tag("$"))).peek().map(|_| Vec::new()))
+    .parse(input)
+}
 
-            entries
-        },
+fn parse_pwd(input: &str) -> IResult<&str, &str, ErrorTree<&str>> {
+    parse_command("pwd", success(()))
+        .precedes(take_until1("\n").context("output").terminated(tag("\n")))
+        .parse(input)
+}
+
+fn parse_touch(input: &str) -> IResult<&str, ShellCommand<'_>, ErrorTree<&str>> {
+    parse_command(
+        "touch",
+        unsigned()
+            .context("size")
+            .preceded_by(space1)
+            .and(parse_path.context("path")),
     )
+    .map(|(size, path)| ShellCommand::Touch { size, path })
     .parse(input)
 }
 
+fn parse_shell_command(input: &str) -> IResult<&str, ShellCommand<'_>, ErrorTree<&str>> {
+    alt((
+        parse_cd.map(ShellCommand::Cd).context("cd"),
+        parse_ls_command
+            .context("command")
+            .precedes(parse_ls_output.context("output"))
+            .map(ShellCommand::Ls)
+            .context("ls"),
+        parse_pwd.map(ShellCommand::Pwd).context("pwd"),
+        parse_command("mkdir", parse_path.context("path"))
+            .map(ShellCommand::Mkdir)
+            .context("mkdir"),
+        parse_command("rm", parse_path.context("path"))
+            .map(ShellCommand::Rm)
+            .context("rm"),
+        parse_touch.context("touch"),
+    ))
+    .parse(input)
+}
+
+/// The state of the emulated shell: the filesystem, along with the current
+/// and previous working directories.
+#[derive(Debug, Default)]
+struct Shell<'a> {
+    root: Directory<'a>,
+    cwd: Vec<&'a str>,
+    previous: Option<Vec<&'a str>>,
+}
+
+impl<'a> Shell<'a> {
+    fn run(&mut self, command: ShellCommand<'a>) -> anyhow::Result<()> {
+        match command {
+            ShellCommand::Cd(destination) => {
+                let destination = match destination {
+                    Destination::Previous => self
+                        .previous
+                        .clone()
+                        .context("there's no previous directory")?,
+                    Destination::Path(path) => path.resolve(&self.cwd),
+                };
+
+                self.root.directory(&destination)?;
+                self.previous = Some(mem::replace(&mut self.cwd, destination));
+            }
+            ShellCommand::Ls(listing) => self
+                .root
+                .directory_mut(&self.cwd)
+                .context("the current directory no longer exists")?
+                .apply_listing(&listing)?,
+            ShellCommand::Pwd(output) => {
+                let cwd = display_path(&self.cwd);
+                ensure!(
+                    output == cwd,
+                    "pwd printed {output:?}, but the current directory is {cwd:?}"
+                );
+            }
+            ShellCommand::Mkdir(path) => self.root.make_directory(&path.resolve(&self.cwd))?,
+            ShellCommand::Rm(path) => {
+                let path = path.resolve(&self.cwd);
+                ensure!(
+                    !self.cwd.starts_with(&path),
+                    "can't remove {}, because it contains the current directory",
+                    display_path(&path)
+                );
+                self.root.remove(&path)?;
+            }
+            ShellCommand::Touch { size, path } => {
+                self.root.write_file(&path.resolve(&self.cwd), size)?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Run every command in a terminal transcript through the shell emulator,
+/// and return the resulting filesystem. Commands that are inconsistent with
+/// the filesystem are reported as errors at the location of the command.
 fn parse_directory_from_instructions(
     mut input: &str,
 ) -> IResult<&str, Directory<'_>, ErrorTree<&str>> {
-    let mut root = Directory::default();
-    let mut path = Vec::new();
-
-    let mut current_dir = &mut root;
+    let mut shell = Shell::default();
 
     loop {
-        let cd_err = match parse_cd.context("cd").parse(input) {
-            Ok((tail, destination)) => {
-                match destination {
-                    Destination::Root => {
-                        path.clear();
-                        current_dir = &mut root;
-                    }
-                    Destination::Up => {
-                        let _ = path.pop();
-                        current_dir = path.iter().fold(&mut root, |dir, name| {
-                            match dir.entries.get_mut(name) {
-                                None => panic!(
-                                    "directory {name:?} doesn't exist; this shouldn't happen here"
-                                ),
-                                Some(Node::File(_)) => {
-                                    panic!("{name:?} is a file, not a directory")
-                                }
-                                Some(Node::Directory(dir)) => dir,
-                            }
-                        });
-                    }
-                    Destination::Directory(name) => {
-                        path.push(name);
-                        current_dir = match current_dir.entries.get_mut(name) {
-                            None => {
-                                return Err(nom::Err::Failure(ErrorTree::from_external_error(
-                                    input,
-                                    ErrorKind::MapRes,
-                                    anyhow::anyhow!("directory {name} doesn't exist"),
-                                )))
-                            }
-                            Some(Node::File(_)) => {
-                                return Err(nom::Err::Failure(ErrorTree::from_external_error(
-                                    input,
-                                    ErrorKind::MapRes,
-                                    anyhow::anyhow!("{name:?} is a file"),
-                                )))
-                            }
-                            Some(Node::Directory(directory)) => directory,
-                        };
-                    }
-                }
-
-                input = tail;
-                continue;
-            }
-            Err(nom::Err::Error(cd_err)) => cd_err,
-            Err(err) => return Err(err),
-        };
+        let command_err = match parse_shell_command.parse(input) {
+            Ok((tail, command)) => {
+                shell.run(command).map_err(|err| {
+                    nom::Err::Failure(ErrorTree::from_external_error(
+                        input,
+                        ErrorKind::Verify,
+                        err,
+                    ))
+                })?;
 
-        let ls_err = match parse_ls_command
-            .context("command")
-            .precedes(parse_ls_output.context("output"))
-            .context("ls")
-            .parse(input)
-        {
-            Ok((tail, directory)) => {
-                *current_dir = directory;
                 input = tail;
                 continue;
             }
@@ -221,8 +417,8 @@ fn parse_directory_from_instructions(
         };
 
         return match eof.value(()).parse(input) {
-            Ok((tail, ())) => Ok((tail, root)),
-            Err(nom::Err::Error(err)) => Err(nom::Err::Error(cd_err.or(ls_err).or(err))),
+            Ok((tail, ())) => Ok((tail, shell.root)),
+            Err(nom::Err::Error(err)) => Err(nom::Err::Error(command_err.or(err))),
             Err(err) => Err(err),
         };
     }
@@ -234,71 +430,695 @@ fn final_parse_directory_from_instructions(
     final_parser(parse_directory_from_instructions)(input)
 }
 
-fn weird_recursive_size(directory: &Directory) -> usize {
-    let size = directory.size();
-    let size = if size <= 100000 { size } else { 0 };
+/// The total size of every directory in the filesystem, keyed by path. These
+/// are all computed in a single post-order pass, so that reports and solutions
+/// never need to recompute the size of a directory.
+#[derive(Debug, Clone, Default)]
+struct DirectorySizes<'a> {
+    sizes: BTreeMap<Vec<&'a str>, usize>,
+}
 
-    directory
-        .entries
-        .values()
-        .filter_map(|node| match node {
-            Node::Directory(dir) => Some(dir),
-            Node::File(_) => None,
-        })
-        .map(weird_recursive_size)
-        .sum::<usize>()
-        + size
+impl<'a> DirectorySizes<'a> {
+    pub fn measure(root: &Directory<'a>) -> Self {
+        let mut sizes = Self::default();
+        sizes.measure_directory(root, &mut Vec::new());
+        sizes
+    }
+
+    fn measure_directory(&mut self, directory: &Directory<'a>, path: &mut Vec<&'a str>) -> usize {
+        let size = directory
+            .entries
+            .iter()
+            .map(|(&name, node)| match node {
+                Node::File(file) => file.size,
+                Node::Directory(child) => {
+                    path.push(name);
+                    let size = self.measure_directory(child, path);
+                    path.pop();
+                    size
+                }
+            })
+            .sum();
+
+        self.sizes.insert(path.clone(), size);
+        size
+    }
+
+    /// The size of the directory at `path`
+    pub fn get(&self, path: &[&'a str]) -> usize {
+        self.sizes
+            .get(path)
+            .copied()
+            .expect("every directory is measured")
+    }
+
+    /// The size of the whole filesystem
+    pub fn total(&self) -> usize {
+        self.get(&[])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[&'a str], usize)> + '_ {
+        self.sizes
+            .iter()
+            .map(|(path, &size)| (path.as_slice(), size))
+    }
+
+    /// Every directory, from largest to smallest
+    pub fn ranked(&self) -> Vec<(&[&'a str], usize)> {
+        let mut ranked: Vec<_> = self.iter().collect();
+        ranked.sort_by_key(|&(path, size)| (Reverse(size), path));
+        ranked
+    }
 }
 
-pub fn part1(input: &str) -> anyhow::Result<usize> {
-    let directory =
-        final_parse_directory_from_instructions(input).context("failed to parse input")?;
+/// Format a size the way `du -h` does: in powers of 1024, rounded up, with a
+/// single decimal place for small values.
+fn human_size(size: usize) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+
+    if size < 1024 {
+        return size.to_string();
+    }
 
-    Ok(weird_recursive_size(&directory))
+    let mut value = size as f64;
+    let mut unit = "";
+
+    for next_unit in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+
+        value /= 1024.0;
+        unit = next_unit;
+    }
+
+    let tenths = (value * 10.0).ceil() / 10.0;
+
+    if tenths < 10.0 {
+        format!("{tenths:.1}{unit}")
+    } else {
+        format!("{:.0}{unit}", value.ceil())
+    }
 }
 
-fn walk_directories<'a, 'n>(
-    name: &'n str,
-    root: &'a Directory<'n>,
-    scan: &mut impl FnMut(&'n str, &'a Directory<'n>),
+/// Print the filesystem to stderr in the same style as the puzzle
+/// description, along with the size of every directory
+fn print_tree<'a>(
+    name: &str,
+    directory: &Directory<'a>,
+    path: &mut Vec<&'a str>,
+    sizes: &DirectorySizes<'a>,
 ) {
-    scan(name, root);
-    root.entries
-        .iter()
-        .filter_map(|(name, node)| match node {
-            Node::Directory(dir) => Some((name, dir)),
-            Node::File(_) => None,
+    let indent = "  ".repeat(path.len());
+    eprintln!("{indent}- {name} (dir, size={})", sizes.get(path));
+
+    for (&name, node) in &directory.entries {
+        match node {
+            Node::File(file) => eprintln!("{indent}  - {name} (file, size={})", file.size),
+            Node::Directory(child) => {
+                path.push(name);
+                print_tree(name, child, path, sizes);
+                path.pop();
+            }
+        }
+    }
+}
+
+/// The size of the disk, and the thresholds used to pick directories
+#[derive(Debug, Clone, Copy, clap::Args)]
+pub struct DiskPolicy {
+    /// The total capacity of the disk
+    #[clap(long, default_value = "70000000", help_heading = "Day 7")]
+    pub capacity: usize,
+
+    /// The amount of unused space needed for the update
+    #[clap(long, default_value = "30000000", help_heading = "Day 7")]
+    pub required_space: usize,
+
+    /// The largest size of a directory counted by part 1
+    #[clap(long, default_value = "100000", help_heading = "Day 7")]
+    pub small_threshold: usize,
+}
+
+impl DiskPolicy {
+    /// The least amount of space that must be deleted to make room for the
+    /// update, given that `used` space is already in use
+    fn min_deletion(&self, used: usize) -> anyhow::Result<usize> {
+        let unused = self.capacity.checked_sub(used).with_context(|| {
+            format!(
+                "{used} space is in use, which exceeds the disk capacity of {}",
+                self.capacity
+            )
+        })?;
+
+        eprintln!("Used: {used}");
+        eprintln!("Unused: {unused}");
+
+        match self.required_space.checked_sub(unused) {
+            Some(0) | None => bail!(
+                "{unused} space is already unused, which is enough for the {} required",
+                self.required_space
+            ),
+            Some(min_deletion) => Ok(min_deletion),
+        }
+    }
+}
+
+/// A kind of entry in a tree description
+#[derive(Debug, Clone, Copy)]
+enum DescribedKind {
+    Directory(Option<usize>),
+    File(usize),
+}
+
+/// Parse a line of a tree description, in the same style as the puzzle
+/// description: `- name (dir)` or `- name (file, size=N)`, indented by two
+/// spaces per level. Directories may also be given a size, which is checked.
+fn parse_description_line(
+    input: &str,
+) -> IResult<&str, (usize, &str, DescribedKind), ErrorTree<&str>> {
+    let size = || tag(", size=").precedes(unsigned()).context("size");
+
+    many0_count(tag("  "))
+        .terminated(tag("- "))
+        .and(take_until1(" (").context("name"))
+        .and(
+            alt((
+                tag("dir")
+                    .precedes(size().opt())
+                    .map(DescribedKind::Directory),
+                tag("file").precedes(size()).map(DescribedKind::File),
+            ))
+            .preceded_by(tag(" ("))
+            .terminated(tag(")"))
+            .context("kind"),
+        )
+        .map(|((depth, name), kind)| (depth, name, kind))
+        .parse(input)
+}
+
+/// A filesystem built from a tree description
+struct Description<'a> {
+    root: Directory<'a>,
+
+    /// Every directory size that was given in the description
+    stated_sizes: Vec<(Vec<&'a str>, usize)>,
+}
+
+/// Build a filesystem from a tree description
+fn parse_description(description: &str) -> anyhow::Result<Description<'_>> {
+    let mut root = Directory::default();
+    let mut stated_sizes = Vec::new();
+
+    // The path of the most recently described directory
+    let mut path: Vec<&str> = Vec::new();
+    let mut lines = description
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let parse_line = |index: usize, line| {
+        let parsed: Result<_, ErrorTree<Location>> = final_parser(parse_description_line)(line);
+        parsed.with_context(|| format!("failed to parse description on line {}", index + 1))
+    };
+
+    let (index, line) = lines.next().context("tree description is empty")?;
+    match parse_line(index, line)? {
+        (0, "/", DescribedKind::Directory(size)) => {
+            stated_sizes.extend(size.map(|size| (Vec::new(), size)))
+        }
+        _ => bail!("tree description must start with \"- / (dir)\""),
+    }
+
+    for (index, line) in lines {
+        let (depth, name, kind) = parse_line(index, line)?;
+
+        ensure!(
+            (1..=path.len() + 1).contains(&depth),
+            "line {} is indented too far",
+            index + 1,
+        );
+
+        ensure!(
+            !matches!(name, "." | "..") && !name.contains('/') && name.trim() == name,
+            "line {}: {name:?} is not a valid name",
+            index + 1,
+        );
+
+        path.truncate(depth - 1);
+        path.push(name);
+
+        match kind {
+            DescribedKind::Directory(size) => {
+                root.make_directory(&path)
+                    .with_context(|| format!("failed to create directory on line {}", index + 1))?;
+                stated_sizes.extend(size.map(|size| (path.clone(), size)));
+            }
+            DescribedKind::File(size) => {
+                ensure!(
+                    !root
+                        .directory(&path[..depth - 1])?
+                        .entries
+                        .contains_key(name),
+                    "line {}: {} already exists",
+                    index + 1,
+                    display_path(&path)
+                );
+                root.write_file(&path, size)
+                    .with_context(|| format!("failed to create file on line {}", index + 1))?;
+                path.pop();
+            }
+        }
+    }
+
+    Ok(Description { root, stated_sizes })
+}
+
+/// Describe a real directory on disk, in the tree description format.
+/// Anything that isn't a regular file or a directory, such as a symlink, is
+/// skipped.
+fn describe_directory(root: &Path) -> anyhow::Result<String> {
+    fn describe(path: &Path, depth: usize, description: &mut String) -> anyhow::Result<()> {
+        let mut entries: Vec<_> = fs::read_dir(path)
+            .with_context(|| format!("failed to read directory {:?}", path.display()))?
+            .collect::<Result<_, _>>()
+            .with_context(|| format!("failed to read directory {:?}", path.display()))?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let indent = "  ".repeat(depth);
+
+        for entry in entries {
+            let entry_path = entry.path();
+            let name = entry.file_name();
+            let name = name
+                .to_str()
+                .with_context(|| format!("{:?} is not valid UTF-8", entry_path.display()))?;
+
+            ensure!(
+                !name.contains('\n') && name.trim() == name && !name.contains(" ("),
+                "{:?} can't be represented in a transcript",
+                entry_path.display()
+            );
+
+            let file_type = entry
+                .file_type()
+                .with_context(|| format!("failed to inspect {:?}", entry_path.display()))?;
+
+            if file_type.is_dir() {
+                writeln!(description, "{indent}- {name} (dir)")?;
+                describe(&entry_path, depth + 1, description)?;
+            } else if file_type.is_file() {
+                let size = entry
+                    .metadata()
+                    .with_context(|| format!("failed to inspect {:?}", entry_path.display()))?
+                    .len();
+                writeln!(description, "{indent}- {name} (file, size={size})")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut description = String::from("- / (dir)\n");
+    describe(root, 1, &mut description)?;
+    Ok(description)
+}
+
+/// The order in which a generated transcript visits directories
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Traversal {
+    /// Visit each directory with relative `cd`s, returning with `cd ..`
+    DepthFirst,
+
+    /// Visit each level of the tree in turn, with absolute `cd`s
+    BreadthFirst,
+}
+
+/// Options for generating a transcript, rather than reading one
+#[derive(Debug, Clone, clap::Args)]
+pub struct GeneratorOptions {
+    /// Generate a transcript from this directory, or from this tree
+    /// description if it's a file, and solve that instead of the puzzle
+    /// input. The generated transcript is checked by parsing it again.
+    #[clap(long, value_name = "PATH", help_heading = "Day 7")]
+    pub generate_from: Option<PathBuf>,
+
+    /// Write the generated transcript to this file, rather than stderr
+    #[clap(
+        long,
+        value_name = "FILE",
+        requires = "generate_from",
+        help_heading = "Day 7"
+    )]
+    pub transcript_out: Option<PathBuf>,
+
+    /// The order in which the generated transcript visits directories
+    #[clap(
+        long,
+        value_enum,
+        default_value = "depth-first",
+        help_heading = "Day 7"
+    )]
+    pub traversal: Traversal,
+
+    /// How many extra times the generated transcript lists each directory,
+    /// after visiting all of its subdirectories
+    #[clap(long, value_name = "N", default_value = "0", help_heading = "Day 7")]
+    pub redundant_ls: usize,
+}
+
+impl GeneratorOptions {
+    /// Generate a transcript that visits and lists every directory in `root`
+    fn transcript(&self, root: &Directory) -> String {
+        let mut transcript = String::new();
+
+        match self.traversal {
+            Traversal::DepthFirst => self.depth_first("/", root, &mut transcript),
+            Traversal::BreadthFirst => self.breadth_first(root, &mut transcript),
+        }
+        .expect("writing to a String can't fail");
+
+        transcript
+    }
+
+    fn list(directory: &Directory, transcript: &mut String) -> fmt::Result {
+        writeln!(transcript, "$ ls")?;
+
+        directory
+            .entries
+            .iter()
+            .try_for_each(|(name, node)| match node {
+                Node::File(file) => writeln!(transcript, "{} {name}", file.size),
+                Node::Directory(_) => writeln!(transcript, "dir {name}"),
+            })
+    }
+
+    fn subdirectories<'d, 'a>(
+        directory: &'d Directory<'a>,
+    ) -> impl Iterator<Item = (&'a str, &'d Directory<'a>)> {
+        directory
+            .entries
+            .iter()
+            .filter_map(|(&name, node)| match node {
+                Node::Directory(child) => Some((name, child)),
+                Node::File(_) => None,
+            })
+    }
+
+    fn depth_first(
+        &self,
+        name: &str,
+        directory: &Directory,
+        transcript: &mut String,
+    ) -> fmt::Result {
+        // A bare `-` would mean the previous directory
+        match name {
+            "-" => writeln!(transcript, "$ cd ./-")?,
+            name => writeln!(transcript, "$ cd {name}")?,
+        }
+
+        Self::list(directory, transcript)?;
+
+        for (name, child) in Self::subdirectories(directory) {
+            self.depth_first(name, child, transcript)?;
+            writeln!(transcript, "$ cd ..")?;
+        }
+
+        (0..self.redundant_ls).try_for_each(|_| Self::list(directory, transcript))
+    }
+
+    fn breadth_first(&self, root: &Directory, transcript: &mut String) -> fmt::Result {
+        let mut queue = VecDeque::from([(Vec::new(), root)]);
+        let mut visited = Vec::new();
+
+        while let Some((path, directory)) = queue.pop_front() {
+            writeln!(transcript, "$ cd {}", display_path(&path))?;
+            Self::list(directory, transcript)?;
+
+            queue.extend(Self::subdirectories(directory).map(|(name, child)| {
+                let mut child_path = path.clone();
+                child_path.push(name);
+                (child_path, child)
+            }));
+
+            visited.push((path, directory));
+        }
+
+        // Return to each directory after all of its subdirectories have been
+        // visited, so that the extra listings come after the deepest ones
+        if self.redundant_ls > 0 {
+            for (path, directory) in visited.iter().rev() {
+                writeln!(transcript, "$ cd {}", display_path(path))?;
+                (0..self.redundant_ls).try_for_each(|_| Self::list(directory, transcript))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Generate a transcript from a directory, or from a tree description
+    /// file
+    fn generate(&self, source: &Path) -> anyhow::Result<String> {
+        let description = if source.is_dir() {
+            describe_directory(source)?
+        } else {
+            fs::read_to_string(source).with_context(|| {
+                format!("failed to read tree description {:?}", source.display())
+            })?
+        };
+
+        self.generate_from_description(&description)
+    }
+
+    /// Generate a transcript from a tree description, and check that parsing
+    /// it reproduces the same directory sizes
+    fn generate_from_description(&self, description: &str) -> anyhow::Result<String> {
+        let Description { root, stated_sizes } = parse_description(description)?;
+        let expected = DirectorySizes::measure(&root);
+
+        for (path, size) in stated_sizes {
+            ensure!(
+                expected.get(&path) == size,
+                "{} is described with size {size}, but its contents total {}",
+                display_path(&path),
+                expected.get(&path)
+            );
+        }
+
+        let transcript = self.transcript(&root);
+
+        let parsed = final_parse_directory_from_instructions(&transcript)
+            .context("failed to parse the generated transcript")?;
+        let actual = DirectorySizes::measure(&parsed);
+
+        if let Some((path, size)) = expected
+            .iter()
+            .find(|&(path, size)| actual.sizes.get(path) != Some(&size))
+        {
+            match actual.sizes.get(path) {
+                None => bail!(
+                    "{} is missing from the generated transcript",
+                    display_path(path)
+                ),
+                Some(actual) => bail!(
+                    "{} has size {size}, but {actual} in the generated transcript",
+                    display_path(path)
+                ),
+            }
+        }
+
+        if let Some((path, _)) = actual
+            .iter()
+            .find(|(path, _)| !expected.sizes.contains_key(*path))
+        {
+            bail!(
+                "{} appears in the generated transcript, but not the source",
+                display_path(path)
+            );
+        }
+
+        Ok(transcript)
+    }
+}
+
+pub struct Input<'a> {
+    text: Cow<'a, str>,
+    policy: DiskPolicy,
+    tree: bool,
+    du: bool,
+    largest: Option<usize>,
+}
+
+impl<'a> FromPuzzleInput<'a> for Input<'a> {
+    fn from_puzzle_input(input: &'a mut PuzzleInput, options: &'a Options) -> anyhow::Result<Self> {
+        Ok(Self {
+            text: match options.generator.generate_from {
+                None => Cow::Borrowed(input.text()?),
+                Some(ref source) => {
+                    let transcript = options
+                        .generator
+                        .generate(source)
+                        .context("failed to generate transcript")?;
+
+                    match options.generator.transcript_out {
+                        None => eprint!("{transcript}"),
+                        Some(ref path) => fs::write(path, &transcript).with_context(|| {
+                            format!("failed to write transcript to {:?}", path.display())
+                        })?,
+                    }
+
+                    Cow::Owned(transcript)
+                }
+            },
+            policy: options.disk_policy,
+            tree: options.tree,
+            du: options.du,
+            largest: options.largest,
         })
-        .for_each(|(name, dir)| walk_directories(name, dir, scan))
+    }
 }
 
-pub fn part2(input: &str) -> anyhow::Result<usize> {
-    let directory =
-        final_parse_directory_from_instructions(input).context("failed to parse input")?;
+impl Input<'_> {
+    /// Build the filesystem and measure it, printing any requested reports
+    fn measure(&self) -> anyhow::Result<DirectorySizes<'_>> {
+        let directory =
+            final_parse_directory_from_instructions(&self.text).context("failed to parse input")?;
+        let sizes = DirectorySizes::measure(&directory);
+
+        if self.tree {
+            print_tree("/", &directory, &mut Vec::new(), &sizes);
+        }
+
+        if self.du {
+            sizes.ranked().iter().for_each(|&(path, size)| {
+                eprintln!("{}\t{}", human_size(size), display_path(path))
+            });
+        }
 
-    let total_space = 70_000_000;
-    let used_space = directory.size();
-    eprintln!("Used: {used_space}");
-    let unused_space = total_space - used_space;
+        if let Some(count) = self.largest {
+            eprintln!("{count} largest directories:");
+            sizes
+                .ranked()
+                .iter()
+                .take(count)
+                .for_each(|&(path, size)| eprintln!("{size:>12} {}", display_path(path)));
+        }
 
-    eprintln!("Unused: {unused_space}");
-    let required_space = 30_000_000;
-    let min_deletion = required_space - unused_space;
+        Ok(sizes)
+    }
+}
+
+pub fn part1(input: Input) -> anyhow::Result<usize> {
+    let sizes = input.measure()?;
+
+    Ok(sizes
+        .iter()
+        .map(|(_, size)| size)
+        .filter(|&size| size <= input.policy.small_threshold)
+        .sum())
+}
+
+pub fn part2(input: Input) -> anyhow::Result<usize> {
+    let sizes = input.measure()?;
+
+    let min_deletion = input.policy.min_deletion(sizes.total())?;
     eprintln!("Min deletion: {min_deletion}");
 
-    let mut best_dir = None;
+    let (path, size) = sizes
+        .iter()
+        .filter(|&(_, size)| size >= min_deletion)
+        .min_by_key(|&(_, size)| size)
+        .context("No directory was large enough to delete")?;
 
-    walk_directories("/", &directory, &mut |_, dir| {
-        let size = dir.size();
+    eprintln!("Delete: {}", display_path(path));
 
-        if size >= min_deletion {
-            match best_dir {
-                None => best_dir = Some(size),
-                Some(best) if size < best => best_dir = Some(size),
-                Some(_) => {}
-            }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tree with names that need care in a transcript: `-` means the
+    /// previous directory to `cd`, and names may contain spaces
+    const DESCRIPTION: &str = "\
+- / (dir)
+  - - (dir)
+    - - (file, size=5)
+    - deeper (dir)
+      - - (dir)
+        - x (file, size=7)
+  - a b (dir)
+    - c.txt (file, size=100)
+  - empty (dir)
+  - top.dat (file, size=1000)
+";
+
+    fn every_generator() -> impl Iterator<Item = GeneratorOptions> {
+        [Traversal::DepthFirst, Traversal::BreadthFirst]
+            .into_iter()
+            .flat_map(|traversal| {
+                [0, 1, 3].map(|redundant_ls| GeneratorOptions {
+                    generate_from: None,
+                    transcript_out: None,
+                    traversal,
+                    redundant_ls,
+                })
+            })
+    }
+
+    /// Generate a transcript from the description with every generator, and
+    /// check that parsing it gives the same sizes as the description
+    fn check_description(description: &str) {
+        let expected = parse_description(description).expect("description should parse");
+        let expected = DirectorySizes::measure(&expected.root);
+
+        for generator in every_generator() {
+            let transcript = generator
+                .generate_from_description(description)
+                .unwrap_or_else(|err| panic!("{generator:?} failed: {err:?}"));
+
+            let parsed = final_parse_directory_from_instructions(&transcript)
+                .unwrap_or_else(|err| panic!("{generator:?} failed:\n{transcript}\n{err}"));
+
+            assert_eq!(
+                DirectorySizes::measure(&parsed).sizes,
+                expected.sizes,
+                "{generator:?} generated:\n{transcript}"
+            );
         }
-    });
+    }
 
-    best_dir.context("No directory was large enough to delete")
+    #[test]
+    fn generated_transcripts_round_trip() {
+        check_description(DESCRIPTION);
+    }
+
+    #[test]
+    fn dash_directory_is_not_cd_previous() {
+        let generator = every_generator().next().expect("there are generators");
+        let transcript = generator
+            .generate_from_description(DESCRIPTION)
+            .expect("generation should succeed");
+
+        assert!(transcript.contains("$ cd ./-\n"));
+        assert!(!transcript.contains("$ cd -\n"));
+    }
+
+    #[test]
+    fn generated_transcripts_from_a_real_directory() {
+        let root = std::env::temp_dir().join(format!("advent2022-day7-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        fs::create_dir_all(root.join("-").join("-")).unwrap();
+        fs::create_dir_all(root.join("a b").join("empty")).unwrap();
+        fs::write(root.join("-").join("file"), "hello").unwrap();
+        fs::write(root.join("-").join("-").join("-"), "1234567").unwrap();
+        fs::write(root.join("top"), "").unwrap();
+
+        let description = describe_directory(&root);
+        fs::remove_dir_all(&root).unwrap();
+
+        check_description(&description.expect("directory should be described"));
+    }
 }
@@ -1,10 +1,15 @@
-use std::collections::BTreeSet;
-
 use anyhow::Context;
-use gridly::{location::RowOrderedLocation, prelude::*};
+use gridly::prelude::*;
 use gridly_grids::VecGrid;
 
-use crate::library::Definitely;
+use crate::{
+    input::{FromPuzzleInput, PuzzleInput},
+    library::{
+        grid::parse_char_grid,
+        image::{heat, Image, ImageOptions, Rgb},
+    },
+    Options,
+};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 struct Tree {
@@ -19,87 +24,335 @@ impl TryFrom<&str> for TreeMap {
     type Error = anyhow::Error;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        VecGrid::new_from_rows(
-            value
-                .lines()
-                .map(|row| row.as_bytes().iter().copied().map(|height| Tree { height })),
-        )
+        parse_char_grid(value, |cell, _| {
+            cell.to_digit(10)
+                .map(|height| Tree {
+                    height: height as u8,
+                })
+                .context("tree height must be a digit")
+        })
         .map(|trees| TreeMap { trees })
-        .context("failed to build map; were all the rows the same length?")
     }
 }
 
-// Known bug: will return
-fn fetch_visible_interior<T: Ord + Copy>(
-    view: impl IntoIterator<Item = (Location, T)>,
-) -> impl Iterator<Item = Location> {
-    let mut state = None;
-    view.into_iter()
-        .filter(move |&(_, tree)| match state {
-            None => {
-                state = Some(tree);
-                true
-            }
-            Some(tallest) if tree > tallest => {
-                state = Some(tree);
-                true
-            }
-            Some(_) => false,
-        })
-        .map(|(loc, _)| loc)
+/// Which edges of the forest a tree can be seen from
+#[derive(Debug, Clone, Copy, Default)]
+struct Visibility {
+    from: [bool; 4],
 }
 
-fn visibility_from<T: LocationComponent + 'static>(
-    grid: &impl Grid<Item = Tree>,
-) -> impl Iterator<Item = Location> + '_ {
-    grid.view::<T>().iter().flat_map(|line| {
-        let mut line1 = line.iter_with_locations().peekable();
+impl Visibility {
+    fn index(direction: Direction) -> usize {
+        match direction {
+            Up => 0,
+            Down => 1,
+            Left => 2,
+            Right => 3,
+        }
+    }
+
+    /// True if this tree can be seen from outside the forest, looking in
+    /// from the edge in `direction`
+    pub fn from(&self, direction: Direction) -> bool {
+        self.from[Self::index(direction)]
+    }
+
+    /// True if this tree can be seen from any edge of the forest
+    pub fn is_visible(&self) -> bool {
+        EACH_DIRECTION
+            .into_iter()
+            .any(|direction| self.from(direction))
+    }
+}
+
+/// The visibility of every tree in the forest, from every direction
+struct VisibilityMap {
+    visibility: VecGrid<Visibility>,
+}
+
+impl VisibilityMap {
+    pub fn new(trees: &VecGrid<Tree>) -> Self {
+        let mut visibility = VecGrid::new(trees.dimensions())
+            .expect("visibility has the same dimensions as the trees");
+
+        for row in trees.rows().iter() {
+            Self::scan(row.iter_with_locations(), Left, &mut visibility);
+            Self::scan(row.iter_with_locations().rev(), Right, &mut visibility);
+        }
+
+        for column in trees.columns().iter() {
+            Self::scan(column.iter_with_locations(), Up, &mut visibility);
+            Self::scan(column.iter_with_locations().rev(), Down, &mut visibility);
+        }
+
+        Self { visibility }
+    }
+
+    /// Mark every tree in `line` that can be seen from its start, which is
+    /// the edge of the forest in `direction`
+    fn scan<'a>(
+        line: impl IntoIterator<Item = (Location, &'a Tree)>,
+        direction: Direction,
+        visibility: &mut VecGrid<Visibility>,
+    ) {
+        let mut tallest: Option<Tree> = None;
+
+        for (location, &tree) in line {
+            if tallest.is_none_or(|tallest| tree > tallest) {
+                tallest = Some(tree);
 
-        let loc1 = line1.peek().map(|&(loc, _)| loc);
-        let rest1 = fetch_visible_interior(line1);
+                visibility
+                    .get_mut(location)
+                    .expect("visibility has the same dimensions as the trees")
+                    .from[Visibility::index(direction)] = true;
+            }
+        }
+    }
+
+    pub fn get(&self, location: Location) -> Visibility {
+        *self
+            .visibility
+            .get(location)
+            .expect("location is in the forest")
+    }
 
-        let mut line2 = line.iter_with_locations().rev().peekable();
-        let loc2 = line2.peek().map(|&(loc, _)| loc);
-        let rest2 = fetch_visible_interior(line2);
+    /// The number of trees that can be seen from any edge of the forest
+    pub fn visible_count(&self) -> usize {
+        self.visibility
+            .rows()
+            .iter()
+            .flat_map(|row| row.iter())
+            .filter(|visibility| visibility.is_visible())
+            .count()
+    }
+}
 
-        [loc1, loc2].into_iter().flatten().chain(rest1).chain(rest2)
+/// The color of a tree that can't be seen from outside the forest; taller
+/// trees are lighter
+fn hidden_color(tree: Tree) -> Rgb {
+    let level = 24 + tree.height * 8;
+    [level, level, level]
+}
+
+/// Render which trees are visible. Hidden trees are gray, and visible trees
+/// are colored by how many edges they can be seen from.
+fn render_visibility(trees: &VecGrid<Tree>, visibility: &VisibilityMap) -> Image {
+    Image::from_grid(trees, |location, &tree| {
+        let visibility = visibility.get(location);
+        let edges = EACH_DIRECTION
+            .into_iter()
+            .filter(|&direction| visibility.from(direction))
+            .count();
+
+        match edges {
+            0 => hidden_color(tree),
+            edges => heat(0.25 + edges as f64 * 0.1875),
+        }
     })
 }
 
-pub fn part1(input: TreeMap) -> Definitely<usize> {
-    let vis1 = visibility_from::<Row>(&input.trees);
-    let vis2 = visibility_from::<Column>(&input.trees);
-    let locations: BTreeSet<RowOrderedLocation> =
-        vis1.chain(vis2).map(RowOrderedLocation::new).collect();
-    Ok(locations.len())
+/// Render the scenic score of every tree as a heat map. Scores are spread
+/// out with a square root, since a few trees have much higher scores than
+/// the rest.
+fn render_scenic_scores(scores: &VecGrid<isize>, best: isize) -> Image {
+    let best = (best.max(1) as f64).sqrt();
+
+    Image::from_grid(scores, |_, &score| heat((score as f64).sqrt() / best))
 }
 
-pub fn part2(input: TreeMap) -> anyhow::Result<isize> {
+pub fn part1(input: Input) -> anyhow::Result<usize> {
+    let visibility = VisibilityMap::new(&input.trees.trees);
+
     input
-        .trees
+        .image
+        .save(|| render_visibility(&input.trees.trees, &visibility))?;
+
+    Ok(visibility.visible_count())
+}
+
+/// Multiply the viewing distance from each tree in `line`, looking back
+/// toward the start of the line, into its scenic score. This keeps a stack of
+/// the trees that could still block the view of later trees, which makes the
+/// whole line O(n).
+fn scan_viewing_distances<'a>(
+    line: impl IntoIterator<Item = (Location, &'a Tree)>,
+    scores: &mut VecGrid<isize>,
+) {
+    // The index and height of each tree that could block the view. Heights
+    // never increase from the bottom of the stack to the top.
+    let mut blockers: Vec<(isize, Tree)> = Vec::new();
+
+    for (index, (location, &tree)) in (0isize..).zip(line) {
+        while blockers.last().is_some_and(|&(_, blocker)| blocker < tree) {
+            blockers.pop();
+        }
+
+        let distance = match blockers.last() {
+            Some(&(blocker_index, _)) => index - blocker_index,
+            None => index,
+        };
+
+        *scores
+            .get_mut(location)
+            .expect("scores have the same dimensions as the trees") *= distance;
+
+        blockers.push((index, tree));
+    }
+}
+
+/// Compute the scenic score of every tree, in O(n) per row and column
+fn scenic_scores(trees: &VecGrid<Tree>) -> VecGrid<isize> {
+    let mut scores = VecGrid::new_fill_copied(trees.dimensions(), 1)
+        .expect("scores have the same dimensions as the trees");
+
+    for row in trees.rows().iter() {
+        scan_viewing_distances(row.iter_with_locations(), &mut scores);
+        scan_viewing_distances(row.iter_with_locations().rev(), &mut scores);
+    }
+
+    for column in trees.columns().iter() {
+        scan_viewing_distances(column.iter_with_locations(), &mut scores);
+        scan_viewing_distances(column.iter_with_locations().rev(), &mut scores);
+    }
+
+    scores
+}
+
+pub struct Input {
+    trees: TreeMap,
+    image: ImageOptions,
+}
+
+impl FromPuzzleInput<'_> for Input {
+    fn from_puzzle_input(input: &mut PuzzleInput, options: &Options) -> anyhow::Result<Self> {
+        Ok(Self {
+            trees: TreeMap::try_from(input.text()?)?,
+            image: options.image.clone(),
+        })
+    }
+}
+
+pub fn part2(input: Input) -> anyhow::Result<isize> {
+    let scores = scenic_scores(&input.trees.trees);
+
+    let best = scores
         .rows()
         .iter()
-        .flat_map(|row| row.iter_with_locations())
-        // For each tree in the forest...
-        .map(|(location, &root)| {
-            EACH_DIRECTION
-                .into_iter()
-                // Count the number of trees in each direction
-                .map(|direction| {
-                    (1isize..)
-                        // Find the distance at which the intercepting tree appears,
-                        // or the edge of the map
-                        .find_map(|distance| {
-                            match input.trees.get(location + (direction * distance)) {
-                                Ok(&tree) => (tree >= root).then_some(distance),
-                                Err(_) => Some(distance - 1),
-                            }
-                        })
-                        .expect("find_map is guaranteed to terminate")
-                })
-                // Find the product of the tree counts from all 4 directions
-                .product()
-        })
+        .flat_map(|row| row.iter().copied())
         .max()
-        .context("there were no trees in the grid")
+        .context("there were no trees in the grid")?;
+
+    input.image.save(|| render_scenic_scores(&scores, best))?;
+
+    Ok(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+    use joinery::JoinableIterator;
+
+    use super::*;
+
+    /// Compute the scenic score of a single tree by walking outward from it in
+    /// every direction. This is O(n) per tree, so it's only
+    /// used as a reference to check `scenic_scores` against.
+    fn reference_scenic_score(trees: &VecGrid<Tree>, location: Location) -> isize {
+        let root = *trees.get(location).expect("location is in the grid");
+
+        EACH_DIRECTION
+            .into_iter()
+            // Count the number of trees in each direction
+            .map(|direction| {
+                (1isize..)
+                    // Find the distance at which the intercepting tree appears,
+                    // or the edge of the map
+                    .find_map(
+                        |distance| match trees.get(location + (direction * distance)) {
+                            Ok(&tree) => (tree >= root).then_some(distance),
+                            Err(_) => Some(distance - 1),
+                        },
+                    )
+                    .expect("find_map is guaranteed to terminate")
+            })
+            // Find the product of the tree counts from all 4 directions
+            .product()
+    }
+
+    /// A small xorshift random number generator, for building random forests
+    struct XorShift {
+        state: u64,
+    }
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.state ^= self.state << 13;
+            self.state ^= self.state >> 7;
+            self.state ^= self.state << 17;
+            self.state
+        }
+
+        /// A random number in `0..bound`
+        fn below(&mut self, bound: u64) -> u64 {
+            self.next() % bound
+        }
+    }
+
+    fn random_forest(rng: &mut XorShift, max_height: u64) -> VecGrid<Tree> {
+        let rows = rng.below(30) as isize + 1;
+        let columns = rng.below(30) as isize + 1;
+
+        let storage: Vec<Tree> = (0..rows * columns)
+            .map(|_| Tree {
+                height: rng.below(max_height) as u8,
+            })
+            .collect();
+
+        VecGrid::new_row_major((Rows(rows), Columns(columns)), storage)
+            .expect("storage has the right size")
+    }
+
+    fn check_scenic_scores(trees: &VecGrid<Tree>) {
+        let scores = scenic_scores(trees);
+
+        for (location, &score) in scores
+            .rows()
+            .iter()
+            .flat_map(|row| row.iter_with_locations())
+        {
+            assert_eq!(
+                score,
+                reference_scenic_score(trees, location),
+                "scenic score at row {}, column {} of forest:\n{}",
+                location.row.0,
+                location.column.0,
+                trees
+                    .rows()
+                    .iter()
+                    .map(|row| row.iter().map(|tree| tree.height.to_string()).join(""))
+                    .join_with('\n')
+            );
+        }
+    }
+
+    #[test]
+    fn scenic_scores_match_reference_on_example() {
+        let trees = TreeMap::try_from("30373\n25512\n65332\n33549\n35390\n").unwrap();
+        check_scenic_scores(&trees.trees);
+    }
+
+    #[test]
+    fn scenic_scores_match_reference_on_random_forests() {
+        let mut rng = XorShift {
+            state: 0x2022_1208_5eed_cafe,
+        };
+
+        // Small ranges of heights lead to lots of ties, which are the
+        // interesting cases, so they get most of the forests
+        for max_height in [1, 2, 2, 3, 3, 4, 10] {
+            for _ in 0..50 {
+                check_scenic_scores(&random_forest(&mut rng, max_height));
+            }
+        }
+    }
 }
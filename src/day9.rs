@@ -1,17 +1,29 @@
-use std::{cmp::max, collections::HashSet, iter};
+use std::{
+    cmp::max,
+    collections::HashSet,
+    fmt::{self, Display},
+    fs::File,
+    io::{self, BufWriter, Write},
+    iter,
+    num::NonZeroUsize,
+    path::PathBuf,
+};
+
+use anyhow::Context;
 
 use gridly::prelude::*;
 use nom::{
     branch::alt,
-    character::complete::{char, digit1, line_ending, multispace0, space1},
-    combinator::eof,
+    character::complete::{char, space1},
     IResult, Parser,
 };
-use nom_supreme::{
-    error::ErrorTree, final_parser::final_parser, multi::collect_separated_terminated, ParserExt,
-};
+use nom_supreme::{error::ErrorTree, final_parser::final_parser, ParserExt};
 
-use crate::{library::Definitely, parser};
+use crate::{
+    input::{FromPuzzleInput, PuzzleInput},
+    library::parse::{lines, unsigned},
+    parser, Options,
+};
 
 struct Command {
     direction: Direction,
@@ -32,19 +44,14 @@ fn parse_command(input: &str) -> IResult<&str, Command, ErrorTree<&str>> {
     parser! {
         parse_direction.context("direction") => direction,
         space1,
-        digit1.parse_from_str_cut().context("distance") => distance;
+        "distance": unsigned() => distance;
         Command { direction, distance }
     }
     .parse(input)
 }
 
 fn parse_command_list(input: &str) -> IResult<&str, Vec<Command>, ErrorTree<&str>> {
-    collect_separated_terminated(
-        parse_command.context("command"),
-        line_ending,
-        multispace0.terminated(eof),
-    )
-    .parse(input)
+    lines(parse_command.context("command")).parse(input)
 }
 
 pub struct CommandList {
@@ -64,23 +71,28 @@ fn diamond_length(vector: Vector) -> isize {
 }
 
 #[derive(Debug)]
-struct Rope<const N: usize> {
-    body: [Location; N],
+struct Rope {
+    body: Vec<Location>,
 }
 
-impl<const N: usize> Rope<N> {
-    pub fn new() -> Self {
+impl Rope {
+    pub fn new(knots: NonZeroUsize) -> Self {
         Self {
-            body: [Location::zero(); N],
+            body: vec![Location::zero(); knots.get()],
         }
     }
 
-    pub fn move_head(&mut self, direction: Direction) {
+    /// Move the head one step, and pull the rest of the rope along behind it.
+    /// Returns the number of knots that moved, which are always the first
+    /// knots of the rope.
+    pub fn move_head(&mut self, direction: Direction) -> usize {
         let mut body = self.body.iter_mut();
-        let Some(head) = body.next() else { return };
+        let Some(head) = body.next() else { return 0 };
         *head += direction;
 
         let mut prev = *head;
+        let mut moved = 1;
+
         for part in body {
             let mut vector = prev - *part;
             if diamond_length(vector) > 1 {
@@ -88,41 +100,362 @@ impl<const N: usize> Rope<N> {
                 vector.columns.0 = vector.columns.0.signum();
                 *part += vector;
                 prev = *part;
+                moved += 1;
             } else {
                 break;
             }
         }
+
+        moved
     }
 
-    pub fn tail(&self) -> Location {
-        self.body
-            .last()
-            .copied()
-            .expect("length must be at least 1")
+    pub fn knots(&self) -> &[Location] {
+        &self.body
     }
 }
 
-fn record_motion<const N: usize>(input: &CommandList) -> usize {
-    let mut rope: Rope<N> = Rope::new();
+/// The name of a knot, the way the puzzle draws it: H for the head, T for the
+/// tail of a rope with only 2 knots, and otherwise its distance from the head
+fn knot_name(index: usize, count: usize) -> String {
+    match index {
+        0 => "H".to_owned(),
+        1 if count == 2 => "T".to_owned(),
+        index => index.to_string(),
+    }
+}
+
+/// The smallest rectangle containing a set of locations
+#[derive(Debug, Clone, Copy)]
+struct BoundingBox {
+    min: Location,
+    max: Location,
+}
+
+impl BoundingBox {
+    pub fn new(locations: impl IntoIterator<Item = Location>) -> Option<Self> {
+        locations.into_iter().fold(None, |bounds, location| {
+            Some(match bounds {
+                None => BoundingBox {
+                    min: location,
+                    max: location,
+                },
+                Some(BoundingBox { min, max }) => BoundingBox {
+                    min: Location::new(min.row.min(location.row), min.column.min(location.column)),
+                    max: Location::new(max.row.max(location.row), max.column.max(location.column)),
+                },
+            })
+        })
+    }
 
-    let locations: HashSet<Location> = input
-        .commands
+    pub fn union(self, other: Self) -> Self {
+        Self::new([self.min, self.max, other.min, other.max]).expect("there are 4 locations")
+    }
+}
+
+impl Display for BoundingBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = self.max - self.min;
+
+        write!(
+            f,
+            "rows {} to {}, columns {} to {} ({} x {})",
+            self.min.row.0,
+            self.max.row.0,
+            self.min.column.0,
+            self.max.column.0,
+            size.rows.0 + 1,
+            size.columns.0 + 1,
+        )
+    }
+}
+
+/// Every location visited by each knot of a rope
+#[derive(Debug)]
+struct Trails {
+    visited: Vec<HashSet<Location>>,
+}
+
+impl Trails {
+    pub fn new(rope: &Rope) -> Self {
+        Self {
+            visited: rope
+                .knots()
+                .iter()
+                .map(|&knot| HashSet::from([knot]))
+                .collect(),
+        }
+    }
+
+    /// Record the locations of the first `moved` knots of the rope, which are
+    /// the only ones that could have moved to a new location
+    pub fn record(&mut self, rope: &Rope, moved: usize) {
+        self.visited
+            .iter_mut()
+            .zip(&rope.knots()[..moved])
+            .for_each(|(visited, &knot)| {
+                visited.insert(knot);
+            });
+    }
+
+    pub fn tail(&self) -> &HashSet<Location> {
+        self.visited.last().expect("ropes have at least 1 knot")
+    }
+
+    /// Print the number of distinct locations visited by each knot, along
+    /// with the bounding box of each knot's trail, to stderr
+    pub fn print_stats(&self) {
+        let count = self.visited.len();
+        let bounds = |visited: &HashSet<Location>| {
+            BoundingBox::new(visited.iter().copied()).expect("every knot visits its start")
+        };
+
+        eprintln!("{:>5} {:>9}  bounding box", "knot", "distinct");
+
+        for (index, visited) in self.visited.iter().enumerate() {
+            eprintln!(
+                "{:>5} {:>9}  {}",
+                knot_name(index, count),
+                visited.len(),
+                bounds(visited)
+            );
+        }
+
+        let overall = self
+            .visited
+            .iter()
+            .map(bounds)
+            .reduce(BoundingBox::union)
+            .expect("ropes have at least 1 knot");
+
+        eprintln!("whole trail: {overall}");
+    }
+}
+
+fn record_motion(commands: &[Command], knots: NonZeroUsize) -> Trails {
+    let mut rope = Rope::new(knots);
+    let mut trails = Trails::new(&rope);
+
+    commands
         .iter()
-        .flat_map(|command| iter::repeat(command.direction).take(command.distance))
-        .map(|movement| {
-            rope.move_head(movement);
-            rope.tail()
+        .flat_map(|command| iter::repeat_n(command.direction, command.distance))
+        .for_each(|movement| {
+            let moved = rope.move_head(movement);
+            trails.record(&rope, moved);
+        });
+
+    trails
+}
+
+/// The symbol for a knot in an animation frame, which is the knot's name if
+/// it's a single character
+fn knot_symbol(index: usize, count: usize) -> char {
+    let name = knot_name(index, count);
+    let mut chars = name.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(symbol), None) => symbol,
+        _ => '*',
+    }
+}
+
+/// How often the animation draws a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Animation {
+    /// Draw the rope after every step of every command
+    Step,
+
+    /// Draw the rope after every command
+    Command,
+}
+
+/// A fixed rectangle of locations that frames are drawn in, one character
+/// per location, in the same style as the puzzle
+struct Viewport {
+    bounds: BoundingBox,
+}
+
+impl Viewport {
+    fn draw(&self, dest: &mut impl Write, cell: impl Fn(Location) -> char) -> io::Result<()> {
+        let BoundingBox { min, max } = self.bounds;
+
+        for row in min.row.0..=max.row.0 {
+            let line: String = (min.column.0..=max.column.0)
+                .map(|column| cell(Row(row) + Column(column)))
+                .collect();
+
+            writeln!(dest, "{line}")?;
+        }
+
+        writeln!(dest)
+    }
+
+    /// Draw every knot of the rope. Knots closer to the head are drawn over
+    /// the ones behind them, and the start is marked with `s`.
+    fn draw_rope(&self, dest: &mut impl Write, rope: &Rope) -> io::Result<()> {
+        let knots = rope.knots();
+
+        self.draw(dest, |location| {
+            match knots.iter().position(|&knot| knot == location) {
+                Some(index) => knot_symbol(index, knots.len()),
+                None if location == Location::zero() => 's',
+                None => '.',
+            }
         })
-        .chain([Location::zero()])
-        .collect();
+    }
+
+    /// Draw every location visited by the tail with `#`, and the start with
+    /// `s`
+    fn draw_visited(&self, dest: &mut impl Write, visited: &HashSet<Location>) -> io::Result<()> {
+        self.draw(dest, |location| {
+            if location == Location::zero() {
+                's'
+            } else if visited.contains(&location) {
+                '#'
+            } else {
+                '.'
+            }
+        })
+    }
+}
+
+/// Simulate the rope again, drawing a frame after each step or command
+fn animate(
+    commands: &[Command],
+    knots: NonZeroUsize,
+    animation: Animation,
+    viewport: &Viewport,
+    dest: &mut impl Write,
+) -> io::Result<()> {
+    let mut rope = Rope::new(knots);
+
+    writeln!(dest, "== Initial State ==\n")?;
+    viewport.draw_rope(dest, &rope)?;
+
+    for command in commands {
+        writeln!(
+            dest,
+            "== {} {} ==\n",
+            direction_name(command.direction),
+            command.distance
+        )?;
+
+        for _ in 0..command.distance {
+            rope.move_head(command.direction);
+
+            if animation == Animation::Step {
+                viewport.draw_rope(dest, &rope)?;
+            }
+        }
+
+        if animation == Animation::Command {
+            viewport.draw_rope(dest, &rope)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The letter used for a direction in the puzzle input
+fn direction_name(direction: Direction) -> char {
+    match direction {
+        Up => 'U',
+        Right => 'R',
+        Down => 'D',
+        Left => 'L',
+    }
+}
+
+pub struct Input {
+    commands: CommandList,
+    knots: Option<NonZeroUsize>,
+    stats: bool,
+    animation: Option<Animation>,
+    tail_map: bool,
+    frames: Option<PathBuf>,
+}
+
+impl FromPuzzleInput<'_> for Input {
+    fn from_puzzle_input(input: &mut PuzzleInput, options: &Options) -> anyhow::Result<Self> {
+        Ok(Self {
+            commands: CommandList::try_from(input.text()?)?,
+            knots: options.knots,
+            stats: options.rope_stats,
+            animation: options.animate,
+            tail_map: options.tail_map,
+            frames: options.frames.clone(),
+        })
+    }
+}
+
+impl Input {
+    /// Draw the requested animation and tail map, to the frames file if
+    /// there is one, or to stderr
+    fn draw(&self, knots: NonZeroUsize, trails: &Trails) -> anyhow::Result<()> {
+        if self.animation.is_none() && !self.tail_map {
+            return Ok(());
+        }
+
+        let mut dest: Box<dyn Write> = match self.frames {
+            None => Box::new(BufWriter::new(io::stderr().lock())),
+            Some(ref path) => {
+                Box::new(BufWriter::new(File::create(path).with_context(|| {
+                    format!("failed to create frames file {:?}", path.display())
+                })?))
+            }
+        };
+
+        let bounds = trails
+            .visited
+            .iter()
+            .flat_map(|visited| visited.iter().copied())
+            .chain([Location::zero()]);
+        let viewport = Viewport {
+            bounds: BoundingBox::new(bounds).expect("the start is always in bounds"),
+        };
+
+        if let Some(animation) = self.animation {
+            animate(
+                &self.commands.commands,
+                knots,
+                animation,
+                &viewport,
+                &mut dest,
+            )
+            .context("failed to write animation")?;
+        }
+
+        if self.tail_map {
+            viewport
+                .draw_visited(&mut dest, trails.tail())
+                .context("failed to write tail map")?;
+        }
+
+        dest.flush().context("failed to write frames")
+    }
+}
+
+/// Count the locations visited by the tail of the rope
+fn solve(input: Input, default_knots: usize) -> anyhow::Result<usize> {
+    let knots = input
+        .knots
+        .or(NonZeroUsize::new(default_knots))
+        .expect("default knot counts aren't 0");
+
+    let trails = record_motion(&input.commands.commands, knots);
+
+    if input.stats {
+        trails.print_stats();
+    }
+
+    input.draw(knots, &trails)?;
 
-    locations.len()
+    Ok(trails.tail().len())
 }
 
-pub fn part1(input: CommandList) -> Definitely<usize> {
-    Ok(record_motion::<2>(&input))
+pub fn part1(input: Input) -> anyhow::Result<usize> {
+    solve(input, 2)
 }
 
-pub fn part2(input: CommandList) -> Definitely<usize> {
-    Ok(record_motion::<10>(&input))
+pub fn part2(input: Input) -> anyhow::Result<usize> {
+    solve(input, 10)
 }
@@ -0,0 +1,96 @@
+use std::{
+    io::{self, BufRead, Cursor, Read},
+    mem,
+};
+
+use anyhow::Context;
+
+use crate::Options;
+
+/// The puzzle input, as given on the command line. Input from a file or stdin
+/// isn't read until a solver asks for it, so that days that opt into
+/// streaming never need to buffer the whole thing.
+pub enum PuzzleInput {
+    Text(String),
+    Reader(Box<dyn BufRead>),
+}
+
+impl PuzzleInput {
+    /// Get the whole input as a string, reading it into memory if necessary
+    pub fn text(&mut self) -> anyhow::Result<&str> {
+        if let PuzzleInput::Reader(reader) = self {
+            let mut buffer = String::new();
+            reader
+                .read_to_string(&mut buffer)
+                .context("failed to read puzzle input")?;
+            *self = PuzzleInput::Text(buffer);
+        }
+
+        match self {
+            PuzzleInput::Text(text) => Ok(text),
+            PuzzleInput::Reader(_) => unreachable!(),
+        }
+    }
+
+    /// Take the input as a stream. If the input was already buffered, the
+    /// stream reads from that buffer.
+    pub fn stream(&mut self) -> InputStream {
+        let reader = match mem::replace(self, PuzzleInput::Text(String::new())) {
+            PuzzleInput::Text(text) => Box::new(Cursor::new(text.into_bytes())),
+            PuzzleInput::Reader(reader) => reader,
+        };
+
+        InputStream { reader }
+    }
+}
+
+/// Puzzle input that is read incrementally. A day opts into streaming by
+/// taking this as the input to its solvers.
+pub struct InputStream {
+    reader: Box<dyn BufRead>,
+}
+
+impl Read for InputStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl BufRead for InputStream {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.reader.consume(amt)
+    }
+}
+
+/// Conversion from the puzzle input into the input type of a day's solvers.
+/// Any type that can be parsed from a `&str` gets the whole input buffered
+/// into memory first. Days with command line options implement this for their
+/// input type directly, so that they can pick out the options they need.
+pub trait FromPuzzleInput<'a>: Sized {
+    fn from_puzzle_input(input: &'a mut PuzzleInput, options: &'a Options) -> anyhow::Result<Self>;
+}
+
+impl<'a, T> FromPuzzleInput<'a> for T
+where
+    T: TryFrom<&'a str>,
+    T::Error: Into<anyhow::Error>,
+{
+    fn from_puzzle_input(
+        input: &'a mut PuzzleInput,
+        _options: &'a Options,
+    ) -> anyhow::Result<Self> {
+        input
+            .text()
+            .and_then(|text| T::try_from(text).map_err(Into::into))
+    }
+}
+
+impl FromPuzzleInput<'_> for InputStream {
+    fn from_puzzle_input(input: &mut PuzzleInput, _options: &Options) -> anyhow::Result<Self> {
+        Ok(input.stream())
+    }
+}
@@ -1,3 +1,9 @@
+pub mod grid;
+pub mod image;
+pub mod interval;
+pub mod parse;
+pub mod search;
+
 use std::{
     cmp::Reverse, collections::HashMap, convert::Infallible, hash::Hash, iter::FusedIterator, mem,
     ops::ControlFlow,
@@ -100,6 +106,8 @@ where
     }
 }
 
+// No day currently splits an iterator into fixed-size chunks
+#[allow(dead_code)]
 #[derive(Debug, Default, Clone)]
 pub struct Chunks<I, const N: usize> {
     iterator: I,
@@ -181,6 +189,7 @@ impl<T: ExactSizeIterator, const N: usize> ExactSizeIterator for Chunks<T, N> {
 }
 
 pub trait IterExt: Iterator + Sized {
+    #[allow(dead_code)]
     fn streaming_chunks<const N: usize>(self) -> Chunks<Self, N> {
         Chunks { iterator: self }
     }
@@ -205,26 +214,146 @@ pub trait IterExt: Iterator + Sized {
 
 impl<T: Iterator + Sized> IterExt for T {}
 
+/// Build a nom parser out of a sequence of steps, followed by an expression
+/// computing the output from the values bound by those steps:
+///
+/// ```ignore
+/// parser! {
+///     tag("move "),
+///     "count": digit1.parse_from_str() => count,
+///     cut,
+///     tag(" from "),
+///     "origin": parse_label => origin,
+///     "comment": parse_comment => comment?;
+///     Command { count, origin, comment }
+/// }
+/// ```
+///
+/// - `parser => name` binds the output of a step to `name`.
+/// - `"label": parser` attaches `"label"` as error context to that step.
+/// - `parser => name?` makes the step optional; `name` is an `Option`.
+/// - `cut` is a cut point: errors in any subsequent step are failures, so
+///   enclosing `alt`s won't try other branches.
+/// - `; try expr` means `expr` returns a `Result`. An `Err` is reported as an
+///   external error at the start of the parser, like `map_res`.
+///
+/// A set of alternatives can be given as brace-delimited sequences separated
+/// by `|`; each one may have its own label, and they're tried in order:
+///
+/// ```ignore
+/// parser! {
+///     "noop": { tag("noop"); Command::Noop }
+///     | "addx": { tag("addx "), cut, parse_value => value; Command::Addx(value) }
+/// }
+/// ```
 #[macro_export]
 macro_rules! parser {
-    (
-        $(
-            $parser:expr $(=> $bind:ident)?
-        ),* ;
-        $map:expr
-    ) => {
-        move |input| -> nom::IResult<_, _, _> {
-            $(
-                let (input, value) = $parser.parse(input)?;
-                $(
-                    let $bind = value;
-                    let value = ();
-                )?
-                let _ = value;
-            )*
+    // A cut point; every subsequent step is cut
+    (@steps [$input:ident $cut:tt] $code:tt cut, $($tail:tt)*) => {
+        $crate::parser!(@steps [$input true] $code $($tail)*)
+    };
 
-            Ok((input, $map))
-        }
+    (@steps $state:tt $code:tt $label:literal : $($tail:tt)*) => {
+        $crate::parser!(@step $state $code [$label] $($tail)*)
+    };
+
+    (@steps $state:tt $code:tt $($tail:tt)*) => {
+        $crate::parser!(@step $state $code [] $($tail)*)
+    };
+
+    (@step $state:tt [$($code:tt)*] $label:tt $parser:expr => $bind:ident ?, $($tail:tt)*) => {
+        $crate::parser!(@steps $state [
+            $($code)* $crate::parser! { @let $state $label [opt] $parser => $bind }
+        ] $($tail)*)
+    };
+
+    (@step $state:tt [$($code:tt)*] $label:tt $parser:expr => $bind:ident ?; $($tail:tt)*) => {
+        $crate::parser!(@body $state [
+            $($code)* $crate::parser! { @let $state $label [opt] $parser => $bind }
+        ] $($tail)*)
+    };
+
+    (@step $state:tt [$($code:tt)*] $label:tt $parser:expr $(=> $bind:ident)?, $($tail:tt)*) => {
+        $crate::parser!(@steps $state [
+            $($code)* $crate::parser! { @let $state $label [] $parser $(=> $bind)? }
+        ] $($tail)*)
+    };
+
+    (@step $state:tt [$($code:tt)*] $label:tt $parser:expr $(=> $bind:ident)?; $($tail:tt)*) => {
+        $crate::parser!(@body $state [
+            $($code)* $crate::parser! { @let $state $label [] $parser $(=> $bind)? }
+        ] $($tail)*)
+    };
+
+    (@let [$input:ident $cut:tt] $label:tt $modifier:tt $parser:expr => $bind:ident) => {
+        let ($input, $bind) = $crate::parser!(@run $input $cut $label $modifier $parser)?;
+    };
+
+    (@let [$input:ident $cut:tt] $label:tt $modifier:tt $parser:expr) => {
+        let ($input, _) = $crate::parser!(@run $input $cut $label $modifier $parser)?;
+    };
+
+    (@run $input:ident $cut:tt [$($label:literal)?] [$($modifier:ident)?] $parser:expr) => {{
+        // Borrow the parser, so that steps can reuse a parser from the
+        // enclosing scope without consuming it. This is a mutable borrow, so
+        // such a parser must be bound with `let mut`.
+        let parser = &mut $parser;
+        let parser = nom_supreme::ParserExt::by_ref(parser);
+        $(let parser = nom_supreme::ParserExt::context(parser, $label);)?
+        $(let parser = nom_supreme::ParserExt::$modifier(parser);)?
+        let mut parser = $crate::parser!(@cut $cut parser);
+        nom::Parser::parse(&mut parser, $input)
+    }};
+
+    (@cut true $parser:ident) => { nom_supreme::ParserExt::cut($parser) };
+    (@cut false $parser:ident) => { $parser };
+
+    (@err true $err:ident) => { nom::Err::Failure($err) };
+    (@err false $err:ident) => { nom::Err::Error($err) };
+
+    (@body [$input:ident $cut:tt] [$($code:tt)*] try $map:expr) => {
+        (move |$input| -> nom::IResult<_, _, _> {
+            let start = $input;
+            $($code)*
+
+            match $map {
+                Ok(value) => Ok(($input, value)),
+                Err(err) => {
+                    let err = nom::error::FromExternalError::from_external_error(
+                        start,
+                        nom::error::ErrorKind::MapRes,
+                        err,
+                    );
+
+                    Err($crate::parser!(@err $cut err))
+                }
+            }
+        })
+    };
+
+    (@body [$input:ident $cut:tt] [$($code:tt)*] $map:expr) => {
+        (move |$input| -> nom::IResult<_, _, _> {
+            $($code)*
+            Ok(($input, $map))
+        })
+    };
+
+    (@branch [$label:literal] $($branch:tt)*) => {
+        nom_supreme::ParserExt::context($crate::parser!($($branch)*), $label)
+    };
+
+    (@branch [] $($branch:tt)*) => {
+        $crate::parser!($($branch)*)
+    };
+
+    ($($($label:literal :)? { $($branch:tt)* })|+) => {
+        nom::branch::alt((
+            $($crate::parser!(@branch [$($label)?] $($branch)*),)+
+        ))
+    };
+
+    ($($body:tt)*) => {
+        $crate::parser!(@steps [input false] [] $($body)*)
     };
 }
 
@@ -390,3 +519,178 @@ where
 }
 
 pub type Definitely<T> = Result<T, Infallible>;
+
+#[cfg(test)]
+mod tests {
+    use nom::{
+        character::complete::{alpha1, digit1},
+        IResult, Parser,
+    };
+    use nom_supreme::{
+        error::{ErrorTree, StackContext},
+        tag::complete::tag,
+        ParserExt,
+    };
+
+    type Result<'a, T> = IResult<&'a str, T, ErrorTree<&'a str>>;
+
+    fn number(input: &str) -> Result<'_, u32> {
+        digit1.parse_from_str().parse(input)
+    }
+
+    fn is_error<T>(result: &Result<'_, T>) -> bool {
+        matches!(result, Err(nom::Err::Error(_)))
+    }
+
+    fn is_failure<T>(result: &Result<'_, T>) -> bool {
+        matches!(result, Err(nom::Err::Failure(_)))
+    }
+
+    /// True if the error was raised inside a step with this label
+    fn has_context<T>(result: &Result<'_, T>, label: &str) -> bool {
+        match result {
+            Err(nom::Err::Error(ErrorTree::Stack { contexts, .. }))
+            | Err(nom::Err::Failure(ErrorTree::Stack { contexts, .. })) => contexts
+                .iter()
+                .any(|(_, context)| matches!(context, StackContext::Context(c) if *c == label)),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn binds_steps() {
+        let mut parser = parser! {
+            tag("("),
+            number => a,
+            tag(","),
+            number => b,
+            tag(")");
+            (a, b)
+        };
+
+        assert_eq!(parser.parse("(1,2) rest").ok(), Some((" rest", (1, 2))));
+    }
+
+    #[test]
+    fn labels_steps() {
+        let mut parser = parser! {
+            tag("move "),
+            "count": number => count;
+            count
+        };
+
+        let result = parser.parse("move x");
+        assert!(is_error(&result));
+        assert!(has_context(&result, "count"));
+    }
+
+    #[test]
+    fn optional_steps() {
+        let mut parser = parser! {
+            alpha1 => name,
+            number => count?;
+            (name, count)
+        };
+
+        assert_eq!(parser.parse("abc12").ok(), Some(("", ("abc", Some(12)))));
+        assert_eq!(parser.parse("abc-").ok(), Some(("-", ("abc", None))));
+    }
+
+    #[test]
+    fn optional_steps_before_more_steps() {
+        let mut parser = parser! {
+            number => count?,
+            alpha1 => name;
+            (count, name)
+        };
+
+        assert_eq!(parser.parse("3abc").ok(), Some(("", (Some(3), "abc"))));
+        assert_eq!(parser.parse("abc").ok(), Some(("", (None, "abc"))));
+    }
+
+    #[test]
+    fn cut_makes_later_steps_failures() {
+        let mut parser = parser! {
+            tag("#"),
+            cut,
+            number => count;
+            count
+        };
+
+        assert_eq!(parser.parse("#5").ok(), Some(("", 5)));
+
+        // Errors before the cut are still recoverable
+        assert!(is_error(&parser.parse("5")));
+        assert!(is_failure(&parser.parse("#x")));
+    }
+
+    #[test]
+    fn try_body() {
+        let mut parser = parser! {
+            number => value;
+            try u8::try_from(value)
+        };
+
+        assert_eq!(parser.parse("200").ok(), Some(("", 200)));
+        assert!(is_error(&parser.parse("300")));
+    }
+
+    #[test]
+    fn try_body_after_cut() {
+        let mut parser = parser! {
+            tag("#"),
+            cut,
+            number => value;
+            try u8::try_from(value)
+        };
+
+        assert_eq!(parser.parse("#200").ok(), Some(("", 200)));
+        assert!(is_failure(&parser.parse("#300")));
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Value<'a> {
+        Number(u32),
+        Word(&'a str),
+    }
+
+    #[test]
+    fn alternatives() {
+        let mut parser = parser! {
+            "number": { number => value; Value::Number(value) }
+            | "word": { alpha1 => word; Value::Word(word) }
+        };
+
+        assert_eq!(parser.parse("12").ok(), Some(("", Value::Number(12))));
+        assert_eq!(parser.parse("ab").ok(), Some(("", Value::Word("ab"))));
+
+        let result = parser.parse("-");
+        assert!(is_error(&result));
+    }
+
+    #[test]
+    fn cut_in_alternative_stops_later_alternatives() {
+        let mut parser = parser! {
+            { tag("#"), cut, number => value; Value::Number(value) }
+            | { alpha1 => word; Value::Word(word) }
+        };
+
+        assert_eq!(parser.parse("#1").ok(), Some(("", Value::Number(1))));
+        assert_eq!(parser.parse("ab").ok(), Some(("", Value::Word("ab"))));
+        assert!(is_failure(&parser.parse("#ab")));
+    }
+
+    #[test]
+    fn reuses_mutable_parser() {
+        let mut item = tag::<_, _, ErrorTree<&str>>("x");
+
+        let mut parser = parser! {
+            item,
+            tag(","),
+            item;
+            ()
+        };
+
+        assert_eq!(parser.parse("x,x").ok(), Some(("", ())));
+    }
+}
@@ -0,0 +1,48 @@
+use anyhow::Context;
+use gridly::prelude::*;
+use gridly_grids::VecGrid;
+
+/// Parse a grid of characters, one row per line, into a `VecGrid`. Each cell
+/// is converted with `parse_cell`, which is given the character and its
+/// location in the grid. Every row must have the same number of cells.
+pub fn parse_char_grid<T>(
+    input: &str,
+    mut parse_cell: impl FnMut(char, Location) -> anyhow::Result<T>,
+) -> anyhow::Result<VecGrid<T>> {
+    let mut storage = Vec::with_capacity(input.len());
+    let mut width = None;
+    let mut height = 0;
+
+    for (row_idx, line) in input.lines().enumerate() {
+        let row_start = storage.len();
+
+        for (column_idx, cell) in line.chars().enumerate() {
+            let location = Location::new(row_idx as isize, column_idx as isize);
+
+            storage.push(parse_cell(cell, location).with_context(|| {
+                format!(
+                    "invalid cell {cell:?} at row {}, column {}",
+                    row_idx + 1,
+                    column_idx + 1
+                )
+            })?);
+        }
+
+        let row_width = storage.len() - row_start;
+
+        match width {
+            None => width = Some(row_width),
+            Some(width) => anyhow::ensure!(
+                width == row_width,
+                "row {} has {row_width} cells, but the rows before it have {width}",
+                row_idx + 1
+            ),
+        }
+
+        height += 1;
+    }
+
+    let dimensions = (Rows(height), Columns(width.unwrap_or(0) as isize));
+
+    VecGrid::new_row_major(dimensions, storage).context("grid is too large")
+}
@@ -0,0 +1,153 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use gridly::prelude::*;
+
+pub type Rgb = [u8; 3];
+
+/// An RGB image, stored in row-major order, which can be written as a binary
+/// PPM or PGM file without any dependencies
+#[derive(Debug, Clone)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Image {
+    /// Create an image filled with a single color
+    pub fn new(width: usize, height: usize, background: Rgb) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![background; width * height],
+        }
+    }
+
+    /// Create an image by computing the color of each pixel, given its row
+    /// and column
+    pub fn from_fn(
+        width: usize,
+        height: usize,
+        mut color: impl FnMut(usize, usize) -> Rgb,
+    ) -> Self {
+        let pixels = (0..height)
+            .flat_map(|row| (0..width).map(move |column| (row, column)))
+            .map(|(row, column)| color(row, column))
+            .collect();
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Create an image from a grid, with one pixel per cell
+    pub fn from_grid<G: Grid>(grid: &G, mut color: impl FnMut(Location, &G::Item) -> Rgb) -> Self {
+        let root = grid.root();
+        let dimensions = grid.dimensions();
+
+        Self::from_fn(
+            dimensions.columns.0 as usize,
+            dimensions.rows.0 as usize,
+            |row, column| {
+                let location = root + Rows(row as isize) + Columns(column as isize);
+                color(
+                    location,
+                    grid.get(location).expect("location is in the grid"),
+                )
+            },
+        )
+    }
+
+    /// Set the color of a single pixel. Pixels outside of the image are
+    /// ignored.
+    pub fn set(&mut self, row: usize, column: usize, color: Rgb) {
+        if row < self.height && column < self.width {
+            self.pixels[row * self.width + column] = color;
+        }
+    }
+
+    /// Scale the image up, so that each pixel becomes a `factor` x `factor`
+    /// square
+    pub fn scaled(&self, factor: NonZeroUsize) -> Self {
+        let factor = factor.get();
+
+        Self::from_fn(self.width * factor, self.height * factor, |row, column| {
+            self.pixels[(row / factor) * self.width + (column / factor)]
+        })
+    }
+
+    /// Write the image as a binary (P6) PPM
+    pub fn write_ppm(&self, mut dest: impl Write) -> io::Result<()> {
+        write!(dest, "P6\n{} {}\n255\n", self.width, self.height)?;
+        self.pixels
+            .iter()
+            .try_for_each(|pixel| dest.write_all(pixel))?;
+        dest.flush()
+    }
+
+    /// Write the image as a binary (P5) PGM, converting each pixel to its
+    /// luminance
+    pub fn write_pgm(&self, mut dest: impl Write) -> io::Result<()> {
+        write!(dest, "P5\n{} {}\n255\n", self.width, self.height)?;
+        self.pixels
+            .iter()
+            .map(|&[r, g, b]| ((299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000) as u8)
+            .try_for_each(|luma| dest.write_all(&[luma]))?;
+        dest.flush()
+    }
+
+    /// Save the image to a file. Files ending in `.pgm` are written in
+    /// grayscale; anything else is written as a color PPM.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let file = File::create(path)
+            .with_context(|| format!("failed to create image file {:?}", path.display()))?;
+        let dest = BufWriter::new(file);
+
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("pgm") => self.write_pgm(dest),
+            _ => self.write_ppm(dest),
+        }
+        .with_context(|| format!("failed to write image file {:?}", path.display()))
+    }
+}
+
+/// A color from a heat map gradient, running from black through red and
+/// yellow to white as `fraction` goes from 0 to 1
+pub fn heat(fraction: f64) -> Rgb {
+    let scaled = fraction.clamp(0.0, 1.0) * 3.0;
+    let channel = |offset: f64| ((scaled - offset).clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    [channel(0.0), channel(1.0), channel(2.0)]
+}
+
+/// Command line options for rendering a day's grid to an image file
+#[derive(Debug, Clone, clap::Args)]
+pub struct ImageOptions {
+    /// Render the puzzle to this image file, as a PPM, or as a grayscale PGM
+    /// if the file name ends in .pgm. Supported by days 8, 12 and 14.
+    #[clap(long = "image", value_name = "FILE", help_heading = "Images")]
+    pub path: Option<PathBuf>,
+
+    /// Draw each cell of the puzzle as a square of this many pixels
+    #[clap(long, default_value = "4", help_heading = "Images")]
+    pub image_scale: NonZeroUsize,
+}
+
+impl ImageOptions {
+    /// If an image file was requested, render the image and save it. The image
+    /// isn't rendered at all if it isn't needed.
+    pub fn save(&self, render: impl FnOnce() -> Image) -> anyhow::Result<()> {
+        match self.path {
+            None => Ok(()),
+            Some(ref path) => render().scaled(self.image_scale).save(path),
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use std::{
+    cmp::{max, min, Ordering},
+    fmt::Debug,
+    iter::FusedIterator,
+    ops::{Add, Sub},
+};
+
+/// A type whose values are discrete points on a line, such that every point
+/// has a successor.
+pub trait Point: Copy + Ord + Debug + Add<Output = Self> + Sub<Output = Self> {
+    const ZERO: Self;
+    const ONE: Self;
+}
+
+macro_rules! impl_point {
+    ($($type:ty)*) => {$(
+        impl Point for $type {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+        }
+    )*};
+}
+
+impl_point! {i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize}
+
+/// Range of points with inclusive min and max. `min <= max` is always true.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Interval<T> {
+    min: T,
+    max: T,
+}
+
+impl<T: Point> Interval<T> {
+    /// Create a new interval, or `None` if `min > max`
+    pub fn new(min: T, max: T) -> Option<Self> {
+        (min <= max).then_some(Self { min, max })
+    }
+
+    pub fn point(point: T) -> Self {
+        Self {
+            min: point,
+            max: point,
+        }
+    }
+
+    pub fn min(&self) -> T {
+        self.min
+    }
+
+    pub fn max(&self) -> T {
+        self.max
+    }
+
+    /// The number of points in the interval
+    pub fn len(&self) -> T {
+        self.max - self.min + T::ONE
+    }
+
+    #[allow(dead_code)]
+    pub fn contains(&self, point: T) -> bool {
+        self.min <= point && point <= self.max
+    }
+
+    /// Returns true if `other` is entirely inside of this interval
+    pub fn contains_interval(&self, other: &Self) -> bool {
+        self.min <= other.min && other.max <= self.max
+    }
+
+    /// Returns true if the intervals share at least one point
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.min <= other.max && other.min <= self.max
+    }
+
+    #[allow(dead_code)]
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        Self::new(max(self.min, other.min), min(self.max, other.max))
+    }
+
+    /// Returns true if the intervals overlap or are directly adjacent, such
+    /// that their union is a single interval
+    fn touches(&self, other: &Self) -> bool {
+        self.min <= other.max + T::ONE && other.min <= self.max + T::ONE
+    }
+}
+
+/// A set of points, stored as a sorted list of disjoint intervals. Adjacent
+/// intervals are always merged, so each set has exactly one representation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IntervalSet<T> {
+    intervals: Vec<Interval<T>>,
+}
+
+impl<T> Default for IntervalSet<T> {
+    fn default() -> Self {
+        Self {
+            intervals: Vec::new(),
+        }
+    }
+}
+
+impl<T: Point> IntervalSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// The disjoint intervals making up this set, in ascending order
+    pub fn intervals(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = Interval<T>> + ExactSizeIterator + '_ {
+        self.intervals.iter().copied()
+    }
+
+    /// The total number of points in the set
+    pub fn len(&self) -> T {
+        self.intervals
+            .iter()
+            .fold(T::ZERO, |total, interval| total + interval.len())
+    }
+
+    /// The smallest interval containing every point in the set
+    #[allow(dead_code)]
+    pub fn span(&self) -> Option<Interval<T>> {
+        let first = self.intervals.first()?;
+        let last = self.intervals.last()?;
+
+        Some(Interval {
+            min: first.min,
+            max: last.max,
+        })
+    }
+
+    /// Index of the interval that contains `point`, or the index where an
+    /// interval containing it would be inserted.
+    #[allow(dead_code)]
+    fn search(&self, point: T) -> Result<usize, usize> {
+        self.intervals.binary_search_by(|interval| {
+            if interval.max < point {
+                Ordering::Less
+            } else if interval.min > point {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn contains(&self, point: T) -> bool {
+        self.search(point).is_ok()
+    }
+
+    /// Add all the points in `interval` to the set, merging it with any
+    /// intervals it overlaps or touches.
+    pub fn insert(&mut self, interval: Interval<T>) {
+        let start = self
+            .intervals
+            .partition_point(|existing| existing.max + T::ONE < interval.min);
+
+        let end = start
+            + self.intervals[start..]
+                .iter()
+                .take_while(|existing| existing.touches(&interval))
+                .count();
+
+        let merged = self.intervals[start..end]
+            .iter()
+            .fold(interval, |merged, existing| Interval {
+                min: min(merged.min, existing.min),
+                max: max(merged.max, existing.max),
+            });
+
+        self.intervals.splice(start..end, [merged]);
+    }
+
+    /// Remove all the points in `interval` from the set, splitting any
+    /// intervals that it only partially covers.
+    pub fn remove(&mut self, interval: Interval<T>) {
+        let start = self
+            .intervals
+            .partition_point(|existing| existing.max < interval.min);
+
+        let end = start
+            + self.intervals[start..]
+                .iter()
+                .take_while(|existing| existing.overlaps(&interval))
+                .count();
+
+        let remnants: Vec<Interval<T>> = self.intervals[start..end]
+            .iter()
+            .flat_map(|existing| {
+                [
+                    (existing.min < interval.min).then(|| Interval {
+                        min: existing.min,
+                        max: interval.min - T::ONE,
+                    }),
+                    (interval.max < existing.max).then(|| Interval {
+                        min: interval.max + T::ONE,
+                        max: existing.max,
+                    }),
+                ]
+            })
+            .flatten()
+            .collect();
+
+        self.intervals.splice(start..end, remnants);
+    }
+
+    #[allow(dead_code)]
+    pub fn union(&self, other: &Self) -> Self {
+        let mut union = self.clone();
+        union.extend(other.intervals());
+        union
+    }
+
+    pub fn subtract(&self, other: &Self) -> Self {
+        let mut difference = self.clone();
+        other
+            .intervals()
+            .for_each(|interval| difference.remove(interval));
+        difference
+    }
+
+    #[allow(dead_code)]
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut lhs = self.intervals().peekable();
+        let mut rhs = other.intervals().peekable();
+        let mut intervals = Vec::new();
+
+        // Classic sorted merge: after comparing the two head intervals, the
+        // one that ends first can't intersect anything else.
+        while let (Some(a), Some(b)) = (lhs.peek(), rhs.peek()) {
+            intervals.extend(a.intersection(b));
+
+            if a.max < b.max {
+                lhs.next();
+            } else {
+                rhs.next();
+            }
+        }
+
+        Self { intervals }
+    }
+
+    /// The intervals between consecutive intervals in this set; that is, the
+    /// points within the span of the set that aren't in the set.
+    pub fn gaps(&self) -> impl FusedIterator<Item = Interval<T>> + '_ {
+        self.intervals.windows(2).map(|pair| Interval {
+            min: pair[0].max + T::ONE,
+            max: pair[1].min - T::ONE,
+        })
+    }
+}
+
+impl<T: Point> Extend<Interval<T>> for IntervalSet<T> {
+    fn extend<I: IntoIterator<Item = Interval<T>>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|interval| self.insert(interval))
+    }
+}
+
+impl<T: Point> FromIterator<Interval<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = Interval<T>>>(iter: I) -> Self {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+/// Find how many of `intervals` cover each point. Returns disjoint intervals
+/// in ascending order, each paired with the number of intervals covering every
+/// point in it. Points that aren't covered at all are omitted.
+pub fn depths<T: Point>(
+    intervals: impl IntoIterator<Item = Interval<T>>,
+) -> Vec<(Interval<T>, usize)> {
+    let mut events: Vec<(T, isize)> = intervals
+        .into_iter()
+        .flat_map(|interval| [(interval.min, 1), (interval.max + T::ONE, -1)])
+        .collect();
+
+    events.sort_unstable();
+
+    let mut segments: Vec<(Interval<T>, usize)> = Vec::new();
+    let mut depth = 0;
+
+    for (idx, &(point, delta)) in events.iter().enumerate() {
+        depth += delta;
+
+        // All the events at a given point are applied before the segment
+        // starting at that point is emitted
+        let Some(&(next, _)) = events.get(idx + 1) else {
+            break;
+        };
+
+        if next == point || depth == 0 {
+            continue;
+        }
+
+        let segment = Interval {
+            min: point,
+            max: next - T::ONE,
+        };
+
+        match segments.last_mut() {
+            Some((last, last_depth))
+                if *last_depth == depth as usize && last.max + T::ONE == point =>
+            {
+                last.max = segment.max
+            }
+            _ => segments.push((segment, depth as usize)),
+        }
+    }
+
+    segments
+}
@@ -0,0 +1,85 @@
+use std::{error::Error, str::FromStr};
+
+use gridly::prelude::*;
+use nom::{
+    branch::alt,
+    character::complete::{char, digit1, line_ending, multispace0, space0},
+    combinator::eof,
+    Parser,
+};
+use nom_supreme::{
+    error::ErrorTree, multi::collect_separated_terminated, tag::complete::tag, ParserExt,
+};
+
+use crate::parser;
+
+/// Parse an unsigned decimal number. Numbers that don't fit in `T` are
+/// failures, rather than errors.
+pub fn unsigned<'a, T>() -> impl Parser<&'a str, T, ErrorTree<&'a str>>
+where
+    T: FromStr,
+    T::Err: Error + Send + Sync + 'static,
+{
+    digit1.parse_from_str_cut()
+}
+
+/// Parse a decimal number with an optional leading `-`. Numbers that don't fit
+/// in `T` are failures, rather than errors.
+pub fn signed<'a, T>() -> impl Parser<&'a str, T, ErrorTree<&'a str>>
+where
+    T: FromStr,
+    T::Err: Error + Send + Sync + 'static,
+{
+    digit1
+        .preceded_by(char('-').opt())
+        .recognize()
+        .parse_from_str_cut()
+}
+
+/// Parse an `x,y` pair as a `Location`, where `x` is the column and `y` is the
+/// row. Each component may be labeled, as in `x=1, y=2`, and whitespace is
+/// allowed after the comma.
+pub fn xy_location<'a>() -> impl Parser<&'a str, Location, ErrorTree<&'a str>> {
+    parser! {
+        "x": signed().preceded_by(tag("x=").opt()) => x,
+        char(','),
+        space0,
+        "y": signed().preceded_by(tag("y=").opt()) => y;
+        Location::new(Row(y), Column(x))
+    }
+}
+
+/// Parse the end of a block of lines: either a blank line, which isn't
+/// consumed, or any trailing whitespace at the end of the input.
+fn end_of_block<'a>() -> impl Parser<&'a str, (), ErrorTree<&'a str>> {
+    alt((
+        multispace0.terminated(eof).value(()),
+        line_ending.terminated(line_ending).peek().value(()),
+    ))
+}
+
+/// Parse a series of items, each on its own line. The series ends at a blank
+/// line or at the end of the input, so `lines` can be nested in `blocks`.
+pub fn lines<'a, T, C>(
+    item: impl Parser<&'a str, T, ErrorTree<&'a str>>,
+) -> impl Parser<&'a str, C, ErrorTree<&'a str>>
+where
+    C: Default + Extend<T>,
+{
+    collect_separated_terminated(item, line_ending, end_of_block())
+}
+
+/// Parse a series of items separated by blank lines, through to the end of
+/// the input. Items shouldn't consume their own trailing line ending.
+pub fn blocks<'a, T, C>(
+    item: impl Parser<&'a str, T, ErrorTree<&'a str>>,
+) -> impl Parser<&'a str, C, ErrorTree<&'a str>>
+where
+    C: Default + Extend<T>,
+{
+    collect_separated_terminated(
+        item,
+        line_ending.terminated(line_ending),
+        multispace0.terminated(eof),
+    )
+}
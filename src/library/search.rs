@@ -0,0 +1,206 @@
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, BinaryHeap, HashMap, VecDeque},
+    hash::Hash,
+    ops::Add,
+};
+
+/// The result of a successful search
+#[derive(Debug, Clone)]
+pub struct Solution<S, C> {
+    /// The total cost of the path
+    pub cost: C,
+
+    /// Every state along the path, starting with one of the start states and
+    /// ending with the goal state
+    pub path: Vec<S>,
+
+    /// The number of states that were expanded (had their neighbors examined)
+    pub visited: usize,
+
+    /// The number of distinct states that were discovered
+    pub discovered: usize,
+}
+
+/// Follow a chain of parents back from `goal` to build the path leading to it.
+fn reconstruct_path<S: Clone + Eq + Hash>(
+    goal: S,
+    mut parent: impl FnMut(&S) -> Option<S>,
+) -> Vec<S> {
+    let mut path = vec![goal];
+
+    while let Some(previous) = parent(path.last().unwrap()) {
+        path.push(previous);
+    }
+
+    path.reverse();
+    path
+}
+
+/// Breadth-first search, where every step costs 1. Searches outward from all
+/// of the `starts` at once and returns the shortest path to any state matching
+/// `is_goal`, or `None` if no such state is reachable.
+pub fn bfs<S, I>(
+    starts: impl IntoIterator<Item = S>,
+    mut neighbors: impl FnMut(&S) -> I,
+    mut is_goal: impl FnMut(&S) -> bool,
+) -> Option<Solution<S, usize>>
+where
+    S: Clone + Eq + Hash,
+    I: IntoIterator<Item = S>,
+{
+    let mut parents: HashMap<S, Option<S>> = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    for start in starts {
+        if let Entry::Vacant(slot) = parents.entry(start.clone()) {
+            slot.insert(None);
+            queue.push_back((start, 0));
+        }
+    }
+
+    let mut visited = 0;
+
+    while let Some((state, cost)) = queue.pop_front() {
+        if is_goal(&state) {
+            return Some(Solution {
+                cost,
+                discovered: parents.len(),
+                path: reconstruct_path(state, |state| parents[state].clone()),
+                visited,
+            });
+        }
+
+        visited += 1;
+
+        for neighbor in neighbors(&state) {
+            if let Entry::Vacant(slot) = parents.entry(neighbor.clone()) {
+                slot.insert(Some(state.clone()));
+                queue.push_back((neighbor, cost + 1));
+            }
+        }
+    }
+
+    None
+}
+
+/// Item in the A* priority queue. Ordered such that the lowest estimated total
+/// cost is the greatest, since `BinaryHeap` is a max heap.
+#[derive(Debug)]
+struct Candidate<S, C> {
+    estimate: C,
+    cost: C,
+    state: S,
+}
+
+impl<S, C: Ord> PartialEq for Candidate<S, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<S, C: Ord> Eq for Candidate<S, C> {}
+
+impl<S, C: Ord> PartialOrd for Candidate<S, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S, C: Ord> Ord for Candidate<S, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Among equal estimates, prefer the more expensive path, since it's
+        // probably closer to the goal
+        Ord::cmp(&other.estimate, &self.estimate).then_with(|| Ord::cmp(&self.cost, &other.cost))
+    }
+}
+
+/// A* search. `neighbors` returns each neighboring state, along with the cost
+/// of moving to it. `heuristic` estimates the remaining cost from a state to
+/// the nearest goal; it must never overestimate, or the returned path might
+/// not be the cheapest one. Searches outward from all of the `starts` at once.
+pub fn astar<S, C, I>(
+    starts: impl IntoIterator<Item = S>,
+    mut neighbors: impl FnMut(&S) -> I,
+    mut heuristic: impl FnMut(&S) -> C,
+    mut is_goal: impl FnMut(&S) -> bool,
+) -> Option<Solution<S, C>>
+where
+    S: Clone + Eq + Hash,
+    C: Copy + Ord + Default + Add<Output = C>,
+    I: IntoIterator<Item = (S, C)>,
+{
+    // For each discovered state, the cheapest known cost to reach it, and the
+    // state it was reached from.
+    let mut best: HashMap<S, (C, Option<S>)> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+
+    for state in starts {
+        best.insert(state.clone(), (C::default(), None));
+        queue.push(Candidate {
+            estimate: heuristic(&state),
+            cost: C::default(),
+            state,
+        });
+    }
+
+    let mut visited = 0;
+
+    while let Some(Candidate { cost, state, .. }) = queue.pop() {
+        // Skip stale queue entries for states we've since found a cheaper
+        // path to.
+        if best[&state].0 < cost {
+            continue;
+        }
+
+        if is_goal(&state) {
+            return Some(Solution {
+                cost,
+                discovered: best.len(),
+                path: reconstruct_path(state, |state| best[state].1.clone()),
+                visited,
+            });
+        }
+
+        visited += 1;
+
+        for (neighbor, step) in neighbors(&state) {
+            let cost = cost + step;
+
+            match best.entry(neighbor.clone()) {
+                Entry::Occupied(slot) if slot.get().0 <= cost => continue,
+                Entry::Occupied(mut slot) => {
+                    slot.insert((cost, Some(state.clone())));
+                }
+                Entry::Vacant(slot) => {
+                    slot.insert((cost, Some(state.clone())));
+                }
+            }
+
+            queue.push(Candidate {
+                estimate: cost + heuristic(&neighbor),
+                cost,
+                state: neighbor,
+            });
+        }
+    }
+
+    None
+}
+
+/// Dijkstra's algorithm; this is A* without a heuristic. `neighbors` returns
+/// each neighboring state, along with the cost of moving to it, which must
+/// not be negative.
+#[allow(dead_code)]
+pub fn dijkstra<S, C, I>(
+    starts: impl IntoIterator<Item = S>,
+    neighbors: impl FnMut(&S) -> I,
+    is_goal: impl FnMut(&S) -> bool,
+) -> Option<Solution<S, C>>
+where
+    S: Clone + Eq + Hash,
+    C: Copy + Ord + Default + Add<Output = C>,
+    I: IntoIterator<Item = (S, C)>,
+{
+    astar(starts, neighbors, |_| C::default(), is_goal)
+}
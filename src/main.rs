@@ -1,11 +1,12 @@
 include!(concat!(env!("OUT_DIR"), "/generated.rs"));
 
+mod input;
 mod library;
 
 use std::{
     fs::File,
-    io::{self, Read},
-    num::ParseIntError,
+    io::{self, BufReader},
+    num::{NonZeroUsize, ParseIntError},
     path::PathBuf,
     str::FromStr,
 };
@@ -14,6 +15,8 @@ use anyhow::Context;
 use clap::Parser;
 use thiserror::Error;
 
+use crate::input::{FromPuzzleInput, PuzzleInput};
+
 #[derive(Debug, Clone, Error)]
 pub enum DayError {
     #[error("Failed to parse day")]
@@ -73,27 +76,143 @@ struct Args {
     /// If given, use this as the puzzle input directly
     #[clap(short, long, group = "input")]
     string: Option<String>,
+
+    #[clap(flatten)]
+    options: Options,
+}
+
+/// Options that only apply to specific days. Each day picks out the ones it
+/// needs when its input is constructed.
+#[derive(Debug, clap::Args)]
+pub struct Options {
+    /// Total the calories of this many of the top elves in part 2, rather
+    /// than 3
+    #[clap(long, value_name = "K", help_heading = "Day 1")]
+    pub top_k: Option<usize>,
+
+    /// Print a report of every elf's calories to stderr
+    #[clap(long, help_heading = "Day 1")]
+    pub calorie_report: bool,
+
+    /// Load the rules of the game from this file, instead of using a preset
+    #[clap(long, value_name = "FILE", help_heading = "Day 2")]
+    pub rules: Option<PathBuf>,
+
+    /// Which built in game rules to use
+    #[clap(
+        long,
+        value_enum,
+        default_value = "classic",
+        conflicts_with = "rules",
+        help_heading = "Day 2"
+    )]
+    pub preset: day2::Preset,
+
+    /// Also score the strategy guide under every possible meaning of its
+    /// second column, and print the results to stderr
+    #[clap(long, help_heading = "Day 2")]
+    pub decode: bool,
+
+    /// The number of elves in each group
+    #[clap(long, default_value = "3", help_heading = "Day 3")]
+    pub group_size: NonZeroUsize,
+
+    /// The number of equally sized compartments in each sack
+    #[clap(long, default_value = "2", help_heading = "Day 3")]
+    pub compartments: NonZeroUsize,
+
+    /// Every valid item, in order of increasing priority, starting from 1.
+    /// Defaults to a-z followed by A-Z.
+    #[clap(long, value_name = "ITEMS", help_heading = "Day 3")]
+    pub priorities: Option<day3::PriorityTable>,
+
+    /// Print an analysis of the whole assignment list to stderr
+    #[clap(long, help_heading = "Day 4")]
+    pub analyze: bool,
+
+    /// Which crane model to use for both parts, rather than the 9000 for
+    /// part 1 and the 9001 for part 2
+    #[clap(long, value_enum, help_heading = "Day 5")]
+    pub crane: Option<day5::Crane>,
+
+    /// The most crates the limited crane can lift at once
+    #[clap(long, default_value = "3", help_heading = "Day 5")]
+    pub max_lift: NonZeroUsize,
+
+    /// Print the stacks to stderr after every command, in the same format as
+    /// the puzzle input
+    #[clap(long, help_heading = "Day 5")]
+    pub trace: bool,
+
+    /// The number of distinct characters in a marker, rather than 4 for
+    /// part 1 or 14 for part 2
+    #[clap(long, help_heading = "Day 6")]
+    pub width: Option<NonZeroUsize>,
+
+    /// List the position of every marker, one per line, rather than just the
+    /// first one
+    #[clap(long, help_heading = "Day 6")]
+    pub all_markers: bool,
+
+    /// Print the whole filesystem to stderr, along with the size of every
+    /// directory
+    #[clap(long, help_heading = "Day 7")]
+    pub tree: bool,
+
+    /// Print the size of every directory to stderr, largest first, in the
+    /// style of `du -h`
+    #[clap(long, help_heading = "Day 7")]
+    pub du: bool,
+
+    /// Print this many of the largest directories to stderr
+    #[clap(long, value_name = "N", help_heading = "Day 7")]
+    pub largest: Option<usize>,
+
+    #[clap(flatten)]
+    pub disk_policy: day7::DiskPolicy,
+
+    #[clap(flatten)]
+    pub generator: day7::GeneratorOptions,
+
+    /// The number of knots in the rope, rather than 2 for part 1 or 10 for
+    /// part 2
+    #[clap(long, help_heading = "Day 9")]
+    pub knots: Option<NonZeroUsize>,
+
+    /// Print the number of distinct locations visited by each knot, and the
+    /// bounding box of its trail, to stderr
+    #[clap(long, help_heading = "Day 9")]
+    pub rope_stats: bool,
+
+    /// Draw the rope after every step or every command, in the same style as
+    /// the puzzle
+    #[clap(long, value_enum, value_name = "EVERY", help_heading = "Day 9")]
+    pub animate: Option<day9::Animation>,
+
+    /// Draw every location visited by the tail, after the rope has finished
+    /// moving
+    #[clap(long, help_heading = "Day 9")]
+    pub tail_map: bool,
+
+    /// Write the animation and tail map to this file, rather than stderr
+    #[clap(long, value_name = "FILE", help_heading = "Day 9")]
+    pub frames: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub image: library::image::ImageOptions,
 }
 
 fn main() -> anyhow::Result<()> {
     let args: Args = Args::parse();
 
-    let buf = match args.string {
-        Some(buf) => buf,
-        None => {
-            let mut buf = String::new();
-            match args.file {
-                Some(file) => File::open(&file)
-                    .with_context(|| format!("failed to open file: {:?}", file.display()))?
-                    .read_to_string(&mut buf)
-                    .context("failed to read puzzle input from file")?,
-                None => io::stdin()
-                    .read_to_string(&mut buf)
-                    .context("failed to read puzzle input from stdin")?,
-            };
-            buf
-        }
+    let mut input = match (args.string, args.file) {
+        (Some(buf), _) => PuzzleInput::Text(buf),
+        (None, Some(file)) => PuzzleInput::Reader(Box::new(BufReader::new(
+            File::open(&file)
+                .with_context(|| format!("failed to open file: {:?}", file.display()))?,
+        ))),
+        (None, None) => PuzzleInput::Reader(Box::new(io::stdin().lock())),
     };
 
-    run_solution(args.day, args.part, &buf)
+    run_solution(args.day, args.part, &mut input, &args.options)
 }